@@ -0,0 +1,759 @@
+//! Surfaces that a [`Bar`] can be drawn on.
+//!
+//! A [`Bar`] lays out and renders its widgets' texts onto the cairo surface of
+//! its [`Backend`]. The [`XcbBackend`] shows the bar in an X window, while the
+//! [`ImageBackend`] draws it into an in-memory image which can be written out
+//! as a PNG. The latter doesn't need an X server, so it can be used to test
+//! rendering or to produce screenshots.
+//!
+//! [`Bar`]: ../bar/struct.Bar.html
+
+use std::fs::File;
+use std::path::Path;
+use std::rc::Rc;
+
+use anyhow::{anyhow, Context, Result};
+use xcb_util::ewmh;
+
+use crate::bar::{Position, Rect};
+
+// The class part of the bar windows' `WM_CLASS`. The instance part is
+// configurable, so that each bar can be told apart.
+const WM_CLASS: &str = "rusty-bar";
+
+/// A surface that a [`Bar`] is drawn on.
+///
+/// [`Bar`]: ../bar/struct.Bar.html
+pub trait Backend {
+    /// Returns the cairo surface that texts are computed and rendered with.
+    fn surface(&self) -> &cairo::Surface;
+
+    /// Moves and resizes the surface to `rect`, in root window coordinates,
+    /// for a bar at `position`.
+    ///
+    /// The contents of the surface may be lost, so the bar redraws itself
+    /// after calling this.
+    fn configure(&mut self, position: &Position, rect: Rect) -> Result<()>;
+
+    /// Makes anything drawn on the surface visible.
+    fn flush(&self);
+
+    /// Shows the window `window` (e.g. a system tray) in the bar, covering
+    /// `rect` of it. This is called again whenever `rect` changes.
+    ///
+    /// Backends that can't show other windows ignore this, which is the
+    /// default.
+    fn embed_window(&mut self, _window: xcb::Window, _rect: Rect) {}
+
+    /// Shows or hides the surface. A hidden surface doesn't reserve any space
+    /// at the edge of the screen.
+    ///
+    /// Backends that can't be hidden ignore this, which is the default.
+    fn set_visible(&mut self, _visible: bool) -> Result<()> {
+        Ok(())
+    }
+
+    /// Updates anything that depends on the size of the screen, which has
+    /// just changed, such as the space the surface reserves at its edge.
+    ///
+    /// Backends that aren't shown on a screen ignore this, which is the
+    /// default.
+    fn screen_changed(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+fn get_root_visual_type(conn: &xcb::Connection, screen: &xcb::Screen<'_>) -> xcb::Visualtype {
+    for root in conn.get_setup().roots() {
+        for allowed_depth in root.allowed_depths() {
+            for visual in allowed_depth.visuals() {
+                if visual.visual_id() == screen.root_visual() {
+                    return visual;
+                }
+            }
+        }
+    }
+    panic!("No visual type found");
+}
+
+// Finds a 32-bit TrueColor visual on the screen, i.e. one with an alpha channel.
+fn get_argb_visual_type(screen: &xcb::Screen<'_>) -> Option<xcb::Visualtype> {
+    for allowed_depth in screen.allowed_depths() {
+        if allowed_depth.depth() != 32 {
+            continue;
+        }
+        for visual in allowed_depth.visuals() {
+            if visual.class() == xcb::VISUAL_CLASS_TRUE_COLOR as u8 {
+                return Some(visual);
+            }
+        }
+    }
+    None
+}
+
+// Whether a compositing manager is running on the given screen, i.e. whether
+// the `_NET_WM_CM_Sn` selection has an owner.
+fn has_compositor(conn: &xcb::Connection, screen_idx: usize) -> bool {
+    let name = format!("_NET_WM_CM_S{screen_idx}");
+    let atom = match xcb::intern_atom(conn, false, &name).get_reply() {
+        Ok(reply) => reply.atom(),
+        Err(_) => return false,
+    };
+    xcb::get_selection_owner(conn, atom)
+        .get_reply()
+        .map(|reply| reply.owner() != xcb::NONE)
+        .unwrap_or(false)
+}
+
+// The visual that a bar's window is created with.
+pub(crate) struct WindowVisual {
+    pub depth: u8,
+    pub visual: xcb::Visualtype,
+    // The colormap created for the visual, if it isn't the root visual.
+    pub colormap: Option<xcb::Colormap>,
+}
+
+// Picks an ARGB visual if a compositor is running, so that transparent parts of
+// the bar show what's behind it. Otherwise, the root visual is used and
+// transparent parts of the bar are black.
+//
+// This is only decided when the window is created, so a compositor that is
+// started later only takes effect for new bars.
+pub(crate) fn choose_visual(
+    conn: &xcb::Connection,
+    screen_idx: usize,
+    screen: &xcb::Screen<'_>,
+) -> WindowVisual {
+    if has_compositor(conn, screen_idx) {
+        if let Some(visual) = get_argb_visual_type(screen) {
+            let colormap = conn.generate_id();
+            xcb::create_colormap(
+                conn,
+                xcb::COLORMAP_ALLOC_NONE as u8,
+                colormap,
+                screen.root(),
+                visual.visual_id(),
+            );
+            return WindowVisual {
+                depth: 32,
+                visual,
+                colormap: Some(colormap),
+            };
+        }
+    }
+
+    WindowVisual {
+        depth: screen.root_depth(),
+        visual: get_root_visual_type(conn, screen),
+        colormap: None,
+    }
+}
+
+/// Creates a `cairo::Surface` for the XCB window with the given `id`.
+fn cairo_surface_for_xcb_window(
+    conn: &xcb::Connection,
+    visual: &mut xcb::Visualtype,
+    id: u32,
+    width: i32,
+    height: i32,
+) -> Result<cairo::XCBSurface> {
+    let cairo_conn = unsafe {
+        cairo::XCBConnection::from_raw_none(conn.get_raw_conn() as *mut cairo_sys::xcb_connection_t)
+    };
+    let visual = unsafe {
+        cairo::XCBVisualType::from_raw_none(
+            &mut visual.base as *mut xcb::ffi::xcb_visualtype_t as *mut cairo_sys::xcb_visualtype_t,
+        )
+    };
+    let drawable = cairo::XCBDrawable(id);
+    let surface = cairo::XCBSurface::create(&cairo_conn, &drawable, &visual, width, height)
+        .map_err(|status| anyhow!("XCBSurface::create: {}", status))?;
+    Ok(surface)
+}
+
+fn create_surface(
+    conn: &xcb::Connection,
+    screen: &xcb::Screen<'_>,
+    window_id: u32,
+    visual: &mut WindowVisual,
+    rect: Rect,
+) -> Result<cairo::XCBSurface> {
+    // With an ARGB visual, start out transparent rather than black. The border
+    // pixel and colormap must be given whenever the visual differs from the
+    // root window's.
+    let back_pixel = if visual.depth == 32 {
+        0
+    } else {
+        screen.black_pixel()
+    };
+    let colormap = visual.colormap.unwrap_or_else(|| screen.default_colormap());
+    let values = [
+        (xcb::CW_BACK_PIXEL, back_pixel),
+        (xcb::CW_BORDER_PIXEL, 0),
+        (
+            xcb::CW_EVENT_MASK,
+            xcb::EVENT_MASK_EXPOSURE
+                | xcb::EVENT_MASK_BUTTON_PRESS
+                | xcb::EVENT_MASK_BUTTON_RELEASE,
+        ),
+        (xcb::CW_COLORMAP, colormap),
+    ];
+
+    xcb::create_window(
+        conn,
+        visual.depth,
+        window_id,
+        screen.root(),
+        rect.x,
+        rect.y,
+        rect.width,
+        rect.height,
+        0,
+        xcb::WINDOW_CLASS_INPUT_OUTPUT as u16,
+        visual.visual.visual_id(),
+        &values,
+    );
+
+    let surface = cairo_surface_for_xcb_window(
+        conn,
+        &mut visual.visual,
+        window_id,
+        i32::from(rect.width),
+        i32::from(rect.height),
+    )?;
+
+    Ok(surface)
+}
+
+/// A [`Backend`] that shows the bar in a dock window on an X server.
+///
+/// The window is destroyed when the backend is dropped.
+pub struct XcbBackend {
+    conn: Rc<ewmh::Connection>,
+    root: xcb::Window,
+    window_id: u32,
+    colormap: Option<xcb::Colormap>,
+    surface: cairo::XCBSurface,
+    // Windows reparented into ours, which must be handed back to the root
+    // window before ours is destroyed.
+    embedded: Vec<xcb::Window>,
+    // The instance name of the window, used for its `WM_NAME` and `WM_CLASS`.
+    instance: String,
+    // Where the window was last configured to be, for reserving space for
+    // it when it's shown again after being hidden.
+    position: Position,
+    rect: Rect,
+    visible: bool,
+}
+
+impl XcbBackend {
+    /// Creates a new (unmapped) window for a bar covering `rect` of the given
+    /// screen.
+    ///
+    /// If a compositor is running, the window has an ARGB visual so that
+    /// transparent and translucent backgrounds show what's behind the bar.
+    /// The window is mapped the first time it is configured.
+    ///
+    /// The window is named `instance`, and its `WM_CLASS` is `instance` and
+    /// `rusty-bar`, which window manager and compositor rules can match on.
+    pub fn new(
+        conn: Rc<ewmh::Connection>,
+        screen_idx: usize,
+        position: &Position,
+        rect: Rect,
+        instance: &str,
+    ) -> Result<XcbBackend> {
+        let window_id = conn.generate_id();
+
+        let (root, surface, colormap) = {
+            let screen = conn
+                .get_setup()
+                .roots()
+                .nth(screen_idx)
+                .ok_or_else(|| anyhow!("Invalid screen"))?;
+            let mut visual = choose_visual(&conn, screen_idx, &screen);
+            let surface = create_surface(&conn, &screen, window_id, &mut visual, rect)?;
+            (screen.root(), surface, visual.colormap)
+        };
+
+        let backend = XcbBackend {
+            conn,
+            root,
+            window_id,
+            colormap,
+            surface,
+            embedded: Vec::new(),
+            instance: instance.to_owned(),
+            position: position.clone(),
+            rect,
+            visible: true,
+        };
+        backend.set_ewmh_properties(position, rect)?;
+
+        // XXX We can't map the window until we've updated the window size, or nothing
+        // gets rendered. I can't tell if this is something we're doing, something Cairo
+        // is doing or something QTile is doing. This'll do for now and we'll see what
+        // it is like with Lanta!
+        // backend.map_window();
+        backend.flush();
+
+        Ok(backend)
+    }
+
+    /// Returns the connection to the X server.
+    pub fn connection(&self) -> &Rc<ewmh::Connection> {
+        &self.conn
+    }
+
+    /// Returns the ID of the bar's window.
+    pub fn window_id(&self) -> u32 {
+        self.window_id
+    }
+
+    fn map_window(&self) {
+        xcb::map_window(&self.conn, self.window_id);
+    }
+
+    // Sets the ICCCM and EWMH properties that identify the window and tell
+    // the window manager how to treat it.
+    fn set_ewmh_properties(&self, position: &Position, rect: Rect) -> Result<()> {
+        self.set_string_property(xcb::ATOM_WM_NAME, &self.instance);
+        ewmh::set_wm_name(&self.conn, self.window_id, &self.instance);
+        // WM_CLASS is the instance and class names, each null-terminated.
+        let class = format!("{}\0{}\0", self.instance, WM_CLASS);
+        self.set_string_property(xcb::ATOM_WM_CLASS, &class);
+
+        // The PID is only meaningful along with the host it's on.
+        let mut hostname = [0u8; 256];
+        if let Ok(hostname) = nix::unistd::gethostname(&mut hostname) {
+            let hostname = hostname.to_string_lossy();
+            self.set_string_property(xcb::ATOM_WM_CLIENT_MACHINE, &hostname);
+            ewmh::set_wm_pid(&self.conn, self.window_id, std::process::id());
+        }
+
+        ewmh::set_wm_window_type(
+            &self.conn,
+            self.window_id,
+            &[self.conn.WM_WINDOW_TYPE_DOCK()],
+        );
+        // Show the bar on all desktops, above other windows.
+        ewmh::set_wm_desktop(&self.conn, self.window_id, 0xFFFFFFFF);
+        ewmh::set_wm_state(
+            &self.conn,
+            self.window_id,
+            &[self.conn.WM_STATE_STICKY(), self.conn.WM_STATE_ABOVE()],
+        );
+
+        // Struts are measured from the edges of the root window, so we need
+        // its current size, which changes when monitors are added or removed.
+        let strut_partial = if self.visible {
+            let root = xcb::get_geometry(&self.conn, self.root)
+                .get_reply()
+                .context("Failed to get root window geometry")?;
+            strut_partial(position, rect, root.width(), root.height())
+        } else {
+            empty_strut_partial()
+        };
+        ewmh::set_wm_strut_partial(&self.conn, self.window_id, strut_partial);
+        Ok(())
+    }
+
+    // Sets a `STRING` property of the window.
+    fn set_string_property(&self, property: xcb::Atom, value: &str) {
+        xcb::change_property(
+            &self.conn,
+            xcb::PROP_MODE_REPLACE as u8,
+            self.window_id,
+            property,
+            xcb::ATOM_STRING,
+            8,
+            value.as_bytes(),
+        );
+    }
+}
+
+// Returns the space that a bar covering `rect` at `position` reserves on a
+// root window of the given size. Only the edge the bar is docked to is
+// reserved, from that edge of the root window to the far side of the bar and
+// along the extent of the bar, so bars that are offset, shorter than the
+// screen or on another monitor reserve just the space they cover.
+fn strut_partial(
+    position: &Position,
+    rect: Rect,
+    root_width: u16,
+    root_height: u16,
+) -> ewmh::StrutPartial {
+    let mut strut_partial = empty_strut_partial();
+
+    let left = i32::from(rect.x).max(0);
+    let top = i32::from(rect.y).max(0);
+    let right = (i32::from(rect.x) + i32::from(rect.width)).min(i32::from(root_width));
+    let bottom = (i32::from(rect.y) + i32::from(rect.height)).min(i32::from(root_height));
+    if right <= left || bottom <= top {
+        // The bar isn't on the screen, so there's nothing to reserve.
+        return strut_partial;
+    }
+    // The start and end coordinates are inclusive.
+    let (start_x, end_x) = (left as u32, (right - 1) as u32);
+    let (start_y, end_y) = (top as u32, (bottom - 1) as u32);
+
+    match position {
+        Position::Top => {
+            strut_partial.top = bottom as u32;
+            strut_partial.top_start_x = start_x;
+            strut_partial.top_end_x = end_x;
+        }
+        Position::Bottom => {
+            strut_partial.bottom = (i32::from(root_height) - top) as u32;
+            strut_partial.bottom_start_x = start_x;
+            strut_partial.bottom_end_x = end_x;
+        }
+        Position::Left => {
+            strut_partial.left = right as u32;
+            strut_partial.left_start_y = start_y;
+            strut_partial.left_end_y = end_y;
+        }
+        Position::Right => {
+            strut_partial.right = (i32::from(root_width) - left) as u32;
+            strut_partial.right_start_y = start_y;
+            strut_partial.right_end_y = end_y;
+        }
+    }
+    strut_partial
+}
+
+// A strut that doesn't reserve any space.
+fn empty_strut_partial() -> ewmh::StrutPartial {
+    ewmh::StrutPartial {
+        left: 0,
+        right: 0,
+        top: 0,
+        bottom: 0,
+        left_start_y: 0,
+        left_end_y: 0,
+        right_start_y: 0,
+        right_end_y: 0,
+        top_start_x: 0,
+        top_end_x: 0,
+        bottom_start_x: 0,
+        bottom_end_x: 0,
+    }
+}
+
+impl Backend for XcbBackend {
+    fn surface(&self) -> &cairo::Surface {
+        &self.surface
+    }
+
+    fn configure(&mut self, position: &Position, rect: Rect) -> Result<()> {
+        // Update the geometry of the XCB window and the size of the Cairo surface.
+        let values = [
+            (xcb::CONFIG_WINDOW_X as u16, i32::from(rect.x) as u32),
+            (xcb::CONFIG_WINDOW_Y as u16, i32::from(rect.y) as u32),
+            (xcb::CONFIG_WINDOW_WIDTH as u16, u32::from(rect.width)),
+            (xcb::CONFIG_WINDOW_HEIGHT as u16, u32::from(rect.height)),
+            (xcb::CONFIG_WINDOW_STACK_MODE as u16, xcb::STACK_MODE_ABOVE),
+        ];
+        xcb::configure_window(&self.conn, self.window_id, &values);
+        if self.visible {
+            self.map_window();
+        }
+        self.position = position.clone();
+        self.rect = rect;
+        self.surface
+            .set_size(i32::from(rect.width), i32::from(rect.height))
+            .map_err(|status| anyhow!("XCBSurface::set_size: {}", status))?;
+
+        // Update EWMH properties - we might need to reserve more or less space.
+        self.set_ewmh_properties(position, rect)?;
+
+        Ok(())
+    }
+
+    fn flush(&self) {
+        self.conn.flush();
+    }
+
+    fn embed_window(&mut self, window: xcb::Window, rect: Rect) {
+        if !self.embedded.contains(&window) {
+            xcb::reparent_window(&self.conn, window, self.window_id, rect.x, rect.y);
+            self.embedded.push(window);
+        }
+
+        // X doesn't allow zero-sized windows.
+        let width = rect.width.max(1);
+        let height = rect.height.max(1);
+        let values = [
+            (xcb::CONFIG_WINDOW_X as u16, i32::from(rect.x) as u32),
+            (xcb::CONFIG_WINDOW_Y as u16, i32::from(rect.y) as u32),
+            (xcb::CONFIG_WINDOW_WIDTH as u16, u32::from(width)),
+            (xcb::CONFIG_WINDOW_HEIGHT as u16, u32::from(height)),
+        ];
+        xcb::configure_window(&self.conn, window, &values);
+        xcb::map_window(&self.conn, window);
+    }
+
+    fn set_visible(&mut self, visible: bool) -> Result<()> {
+        if self.visible == visible {
+            return Ok(());
+        }
+        self.visible = visible;
+        if visible {
+            self.map_window();
+        } else {
+            xcb::unmap_window(&self.conn, self.window_id);
+        }
+        self.set_ewmh_properties(&self.position, self.rect)
+    }
+
+    fn screen_changed(&mut self) -> Result<()> {
+        // The strut is measured from the edges of the root window, so it
+        // changes along with the root window even if the bar stays put.
+        self.set_ewmh_properties(&self.position, self.rect)
+    }
+}
+
+impl Drop for XcbBackend {
+    fn drop(&mut self) {
+        // Destroying our window would destroy the embedded windows too.
+        // (Any that have already gone away just cause an error event).
+        for &window in &self.embedded {
+            xcb::unmap_window(&self.conn, window);
+            xcb::reparent_window(&self.conn, window, self.root, 0, 0);
+        }
+        xcb::destroy_window(&self.conn, self.window_id);
+        if let Some(colormap) = self.colormap {
+            xcb::free_colormap(&self.conn, colormap);
+        }
+        self.flush();
+    }
+}
+
+/// A [`Backend`] that draws the bar into an in-memory image.
+///
+/// Only the size of the rectangle passed to [`Backend::configure()`] is used;
+/// the image always holds just the bar itself. Parts of the bar without a
+/// background color are transparent.
+pub struct ImageBackend {
+    surface: cairo::ImageSurface,
+}
+
+// Creates a transparent image surface with an alpha channel.
+pub(crate) fn create_image_surface(width: u16, height: u16) -> Result<cairo::ImageSurface> {
+    let surface =
+        cairo::ImageSurface::create(cairo::Format::ARgb32, i32::from(width), i32::from(height))
+            .map_err(|status| anyhow!("ImageSurface::create: {}", status))?;
+    Ok(surface)
+}
+
+impl ImageBackend {
+    /// Creates a new image for a bar that is `width` pixels wide.
+    pub fn new(width: u16) -> Result<ImageBackend> {
+        Ok(ImageBackend {
+            surface: create_image_surface(width, 1)?,
+        })
+    }
+
+    /// Returns the image that the bar is drawn on.
+    pub fn image(&self) -> &cairo::ImageSurface {
+        &self.surface
+    }
+
+    /// Writes the image to a PNG file at `path`.
+    pub fn write_png(&self, path: &Path) -> Result<()> {
+        let mut file =
+            File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        self.surface.flush();
+        self.surface
+            .write_to_png(&mut file)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+}
+
+impl Backend for ImageBackend {
+    fn surface(&self) -> &cairo::Surface {
+        &self.surface
+    }
+
+    fn configure(&mut self, _position: &Position, rect: Rect) -> Result<()> {
+        // Image surfaces can't be resized, so replace it with a new one.
+        let size = (i32::from(rect.width), i32::from(rect.height));
+        if (self.surface.width(), self.surface.height()) != size {
+            self.surface = create_image_surface(rect.width, rect.height)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&self) {
+        self.surface.flush();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{create_image_surface, strut_partial, ImageBackend};
+    use crate::bar::{Alignment, Bar, Position, Rect};
+    use crate::text::{Attributes, Color, Font, Padding, Text, VerticalAlignment};
+    use std::env;
+    use std::fs::{self, File};
+    use std::path::{Path, PathBuf};
+
+    // How much each channel of a pixel may differ from the reference image,
+    // e.g. as antialiasing differs between versions of FreeType and cairo.
+    const CHANNEL_TOLERANCE: u8 = 48;
+    // The share of channels that may differ by more than that.
+    const MAX_DIFFERENT: f64 = 0.01;
+
+    fn text(text: &str) -> Text {
+        Text {
+            attr: Attributes {
+                // A specific font rather than an alias like `monospace`, so
+                // that the reference images don't depend on fontconfig.
+                font: Font::new("DejaVu Sans Mono 11"),
+                fg_color: Color::white(),
+                bg_color: None,
+                padding: Padding::new(8.0, 8.0, 2.0, 2.0),
+                valign: VerticalAlignment::Center,
+            },
+            text: text.to_owned(),
+            stretch: false,
+            markup: false,
+        }
+    }
+
+    #[test]
+    fn image_fits_bar() {
+        let area = Rect {
+            width: 300,
+            ..Rect::default()
+        };
+        let mut bar =
+            Bar::with_backend(ImageBackend::new(300).unwrap(), Position::Top, area).unwrap();
+        bar.add_content(Alignment::Left, vec![text("left")])
+            .unwrap();
+        bar.add_content(Alignment::Right, vec![text("right")])
+            .unwrap();
+
+        // Each text is padded by 8px on either side and 2px above and
+        // below, and every character of a monospace font is as wide.
+        let surface = create_image_surface(1, 1).unwrap();
+        let left = text("left").compute(&surface).unwrap();
+        let right = text("right").compute(&surface).unwrap();
+        let char_width = (left.width - 16.0) / 4.0;
+        assert!(char_width > 0.0);
+        assert!((right.width - 16.0 - char_width * 5.0).abs() <= 1.0);
+        assert_eq!(left.height, right.height);
+        assert!(left.height > 4.0);
+
+        let image = bar.backend().image();
+        assert_eq!(image.width(), 300);
+        // The bar is as tall as its texts, including their padding.
+        assert_eq!(image.height(), left.height as i32);
+        assert_matches_reference(image, "image_fits_bar");
+
+        let path = env::temp_dir().join("rusty-bar-image-fits-bar.png");
+        bar.backend().write_png(&path).unwrap();
+        assert!(path.exists());
+        fs::remove_file(path).unwrap();
+    }
+
+    // Returns the path of the reference image called `name`.
+    fn reference_path(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("images")
+            .join(format!("{name}.png"))
+    }
+
+    // Compares `image` with the reference image called `name`, within the
+    // tolerances above.
+    //
+    // How text is rendered depends on the fonts installed, so the comparison
+    // only happens with `RUSTY_BAR_REFERENCE_IMAGES` set. With
+    // `RUSTY_BAR_BLESS` set, the reference image is replaced by `image`
+    // instead, e.g. after a change to how the bar is drawn.
+    fn assert_matches_reference(image: &cairo::ImageSurface, name: &str) {
+        let path = reference_path(name);
+        image.flush();
+        if env::var_os("RUSTY_BAR_BLESS").is_some() {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            image
+                .write_to_png(&mut File::create(&path).unwrap())
+                .unwrap();
+            return;
+        }
+        if env::var_os("RUSTY_BAR_REFERENCE_IMAGES").is_none() {
+            return;
+        }
+
+        let mut file = File::open(&path).unwrap_or_else(|err| {
+            panic!(
+                "Failed to open {} ({err}), run with RUSTY_BAR_BLESS=1 to create it",
+                path.display()
+            )
+        });
+        let reference = cairo::ImageSurface::create_from_png(&mut file).unwrap();
+        assert_eq!(
+            (image.width(), image.height()),
+            (reference.width(), reference.height())
+        );
+        let mut pixels = Vec::new();
+        image.with_data(|data| pixels = data.to_vec()).unwrap();
+        let mut different = 0;
+        reference
+            .with_data(|data| {
+                different = data
+                    .iter()
+                    .zip(&pixels)
+                    .filter(|(a, b)| a.abs_diff(**b) > CHANNEL_TOLERANCE)
+                    .count();
+            })
+            .unwrap();
+        let share = different as f64 / pixels.len() as f64;
+        assert!(
+            share <= MAX_DIFFERENT,
+            "{:.1}% of {} differs from {}",
+            share * 100.0,
+            name,
+            path.display()
+        );
+    }
+
+    #[test]
+    fn struts_cover_bar() {
+        // A bar along the top of the right monitor, below a 20px gap.
+        let rect = Rect {
+            x: 1920,
+            y: 20,
+            width: 1280,
+            height: 30,
+        };
+        let strut = strut_partial(&Position::Top, rect, 3200, 1080);
+        assert_eq!(strut.top, 50);
+        assert_eq!((strut.top_start_x, strut.top_end_x), (1920, 3199));
+        assert_eq!((strut.left, strut.right, strut.bottom), (0, 0, 0));
+
+        // A bottom bar on the left monitor, which is shorter than the screen.
+        let rect = Rect {
+            x: 100,
+            y: 738,
+            width: 800,
+            height: 30,
+        };
+        let strut = strut_partial(&Position::Bottom, rect, 3200, 1080);
+        assert_eq!(strut.bottom, 342);
+        assert_eq!((strut.bottom_start_x, strut.bottom_end_x), (100, 899));
+        assert_eq!(strut.top, 0);
+
+        let rect = Rect {
+            x: 3160,
+            y: 0,
+            width: 40,
+            height: 1080,
+        };
+        let strut = strut_partial(&Position::Right, rect, 3200, 1080);
+        assert_eq!(strut.right, 40);
+        assert_eq!((strut.right_start_y, strut.right_end_y), (0, 1079));
+    }
+}
@@ -1,96 +1,17 @@
 use std::f64;
 use std::rc::Rc;
 
-use anyhow::{anyhow, Context, Result};
-use ordered_float::OrderedFloat;
+use anyhow::Result;
+use log::trace;
 use xcb_util::ewmh;
 
+use crate::backend::{create_image_surface, Backend, XcbBackend};
+use crate::layout::{self, Content, Orientation, Redraw};
+use crate::mouse::{Modifiers, MouseButton, MouseEvent, MouseEventKind};
 use crate::text::{ComputedText, Text};
 // use crate::widgets::{Widget, WidgetList};
 // use crate::xcb::XcbEventStream;
 
-fn get_root_visual_type(conn: &xcb::Connection, screen: &xcb::Screen<'_>) -> xcb::Visualtype {
-    for root in conn.get_setup().roots() {
-        for allowed_depth in root.allowed_depths() {
-            for visual in allowed_depth.visuals() {
-                if visual.visual_id() == screen.root_visual() {
-                    return visual;
-                }
-            }
-        }
-    }
-    panic!("No visual type found");
-}
-
-/// Creates a `cairo::Surface` for the XCB window with the given `id`.
-fn cairo_surface_for_xcb_window(
-    conn: &xcb::Connection,
-    screen: &xcb::Screen<'_>,
-    id: u32,
-    width: i32,
-    height: i32,
-) -> Result<cairo::XCBSurface> {
-    let cairo_conn = unsafe {
-        cairo::XCBConnection::from_raw_none(conn.get_raw_conn() as *mut cairo_sys::xcb_connection_t)
-    };
-    let visual = unsafe {
-        cairo::XCBVisualType::from_raw_none(
-            &mut get_root_visual_type(conn, screen).base as *mut xcb::ffi::xcb_visualtype_t
-                as *mut cairo_sys::xcb_visualtype_t,
-        )
-    };
-    let drawable = cairo::XCBDrawable(id);
-    let surface = cairo::XCBSurface::create(&cairo_conn, &drawable, &visual, width, height)
-        .map_err(|status| anyhow!("XCBSurface::create: {}", status))?;
-    Ok(surface)
-}
-
-fn create_surface(
-    conn: &xcb::Connection,
-    screen_idx: usize,
-    window_id: u32,
-    height: u16,
-    width: Option<u16>,
-    offset: Offset,
-) -> Result<(u16, cairo::XCBSurface)> {
-    let screen = conn
-        .get_setup()
-        .roots()
-        .nth(screen_idx)
-        .ok_or_else(|| anyhow!("Invalid screen"))?;
-    let values = [
-        (xcb::CW_BACK_PIXEL, screen.black_pixel()),
-        (xcb::CW_EVENT_MASK, xcb::EVENT_MASK_EXPOSURE),
-    ];
-
-    let width = width.unwrap_or_else(|| screen.width_in_pixels());
-
-    xcb::create_window(
-        conn,
-        xcb::COPY_FROM_PARENT as u8,
-        window_id,
-        screen.root(),
-        offset.x,
-        offset.y,
-        width,
-        height,
-        0,
-        xcb::WINDOW_CLASS_INPUT_OUTPUT as u16,
-        screen.root_visual(),
-        &values,
-    );
-
-    let surface = cairo_surface_for_xcb_window(
-        conn,
-        &screen,
-        window_id,
-        i32::from(width),
-        i32::from(height),
-    )?;
-
-    Ok((width, surface))
-}
-
 /// An enum specifying the position of the Cnx bar.
 ///
 /// Passed to [`Cnx::new()`] when constructing a [`Cnx`] instance.
@@ -110,6 +31,45 @@ pub enum Position {
     Top,
     /// Position the Cnx bar at the bottom of the screen.
     Bottom,
+    /// Position the Cnx bar at the left edge of the screen, with widgets
+    /// stacked from top to bottom.
+    Left,
+    /// Position the Cnx bar at the right edge of the screen, with widgets
+    /// stacked from top to bottom.
+    Right,
+}
+
+impl Position {
+    pub(crate) fn orientation(&self) -> Orientation {
+        match self {
+            Position::Top | Position::Bottom => Orientation::Horizontal,
+            Position::Left | Position::Right => Orientation::Vertical,
+        }
+    }
+}
+
+/// An enum specifying the zone of the bar a widget is placed in.
+///
+/// Widgets in the `Left` zone are laid out from the left edge of the bar and
+/// widgets in the `Right` zone are laid out against its right edge. Widgets in
+/// the `Center` zone are centered on the bar, regardless of the width of the
+/// other zones. On a vertical bar (see [`Position::Left`]), the `Left` zone is
+/// at the top and the `Right` zone is at the bottom.
+///
+/// Passed to [`WidgetEntry::align()`] when adding a widget to a [`Cnx`]
+/// instance.
+///
+/// [`WidgetEntry::align()`]: struct.WidgetEntry.html#method.align
+/// [`Cnx`]: struct.Cnx.html
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Alignment {
+    /// Place the widget in the left zone of the bar.
+    #[default]
+    Left,
+    /// Place the widget in the center zone of the bar.
+    Center,
+    /// Place the widget in the right zone of the bar.
+    Right,
 }
 
 /// A struct specifying the `x` and `y` offset
@@ -119,166 +79,304 @@ pub struct Offset {
     pub y: i16,
 }
 
-pub struct Bar {
-    position: Position,
+/// A rectangle in the coordinates of the root window.
+///
+/// Used to describe the area of the screen (or monitor) that a bar is placed
+/// in. The bar spans the full `width` of the area and is placed at its top or
+/// bottom edge (or the full `height` of its left or right edge), depending on
+/// its [`Position`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+}
 
-    conn: Rc<ewmh::Connection>,
-    screen_idx: usize,
-    window_id: u32,
+pub struct Bar<B: Backend = XcbBackend> {
+    position: Position,
 
-    surface: cairo::XCBSurface,
-    width: u16,
-    height: u16,
-    offset: Offset,
+    backend: B,
+    // Texts are drawn here first and then copied to the backend's surface in
+    // one go, so that the background and text of a widget never show up
+    // separately (which flickers).
+    buffer: cairo::ImageSurface,
+    // The bar's window, in root window coordinates.
+    rect: Rect,
+    area: Rect,
+    // The size of the bar across, if it doesn't depend on the texts.
+    fixed_breadth: Option<u16>,
+
+    contents: Vec<Content>,
+    // Widgets' windows embedded over their first text, see
+    // `Widget::embedded_window()`.
+    embedded: Vec<(usize, xcb::Window)>,
+}
 
-    contents: Vec<Vec<ComputedText>>,
+// Returns the rectangle of the window of a bar at `position` in `area`, which
+// is `breadth` pixels across (i.e. tall, for a horizontal bar).
+fn window_rect(position: &Position, area: Rect, breadth: u16) -> Rect {
+    let right = i32::from(area.x) + i32::from(area.width);
+    let bottom = i32::from(area.y) + i32::from(area.height);
+    match position {
+        Position::Top => Rect {
+            x: area.x,
+            y: area.y.max(0),
+            width: area.width,
+            height: breadth,
+        },
+        // If we're at the bottom (or right) of the area, the position of the
+        // window depends on its size.
+        Position::Bottom => Rect {
+            x: area.x,
+            y: (bottom - i32::from(breadth)).max(0) as i16,
+            width: area.width,
+            height: breadth,
+        },
+        Position::Left => Rect {
+            x: area.x.max(0),
+            y: area.y,
+            width: breadth,
+            height: area.height,
+        },
+        Position::Right => Rect {
+            x: (right - i32::from(breadth)).max(0) as i16,
+            y: area.y,
+            width: breadth,
+            height: area.height,
+        },
+    }
 }
 
 impl Bar {
-    pub fn new(position: Position, width: Option<u16>, offset: Offset) -> Result<Bar> {
-        let (conn, screen_idx) =
-            xcb::Connection::connect(None).context("Failed to connect to X server")?;
-        let screen_idx = screen_idx as usize;
-        let window_id = conn.generate_id();
-
-        // We don't actually care about how tall our initial window is - we'll resize
+    // Creates a new `Bar` on the given connection, placed in `area`, whose
+    // window has the given instance name.
+    pub fn new(
+        conn: Rc<ewmh::Connection>,
+        screen_idx: usize,
+        position: Position,
+        area: Rect,
+        instance: &str,
+    ) -> Result<Bar> {
+        // We don't actually care about how broad our initial window is - we'll resize
         // our window once we know how big it needs to be. However, it seems to need
         // to be bigger than 0px, or either Xcb/Cairo (or maybe QTile?) gets upset.
-        let height = 1;
-        let (width, surface) = create_surface(&conn, screen_idx, window_id, height, width, offset)?;
-
-        let ewmh_conn = ewmh::Connection::connect(conn)
-            .map_err(|(e, _)| e)
-            .context("Failed to wrap xcb::Connection in ewmh::Connection")?;
-
-        let bar = Bar {
-            conn: Rc::new(ewmh_conn),
-            window_id,
-            screen_idx,
-            surface,
-            width,
-            height,
-            offset,
+        let rect = window_rect(&position, area, 1);
+        let backend = XcbBackend::new(conn, screen_idx, &position, rect, instance)?;
+        Bar::with_backend(backend, position, area)
+    }
+
+    // Returns the connection to the X server.
+    //
+    // The owner of the `Bar` is responsible for polling this for events,
+    // passing each to `Bar::process_event()`.
+    pub fn connection(&self) -> &Rc<ewmh::Connection> {
+        self.backend.connection()
+    }
+
+    // Process an X event received from the `Bar::connection()`.
+    //
+    // If the event is a mouse event on one of the widgets' texts, returns the
+    // index of the widget along with the event, so that the owner of the
+    // `Bar` can pass it on to the widget.
+    //
+    // Events for other windows are ignored.
+    pub fn process_event(
+        &mut self,
+        event: &xcb::GenericEvent,
+    ) -> Result<Option<(usize, MouseEvent)>> {
+        let window_id = self.backend.window_id();
+        match event.response_type() & !0x80 {
+            xcb::EXPOSE => {
+                let event: &xcb::ExposeEvent = unsafe { xcb::cast_event(event) };
+                if event.window() != window_id {
+                    return Ok(None);
+                }
+                // The back buffer is always up to date, so just copy the
+                // exposed area back onto the window.
+                self.present(
+                    f64::from(event.x()),
+                    f64::from(event.y()),
+                    f64::from(event.width()),
+                    f64::from(event.height()),
+                )?;
+                Ok(None)
+            }
+            response_type @ (xcb::BUTTON_PRESS | xcb::BUTTON_RELEASE) => {
+                let kind = if response_type == xcb::BUTTON_PRESS {
+                    MouseEventKind::Press
+                } else {
+                    MouseEventKind::Release
+                };
+                // ButtonReleaseEvent is just an alias of ButtonPressEvent.
+                let event: &xcb::ButtonPressEvent = unsafe { xcb::cast_event(event) };
+                if event.event() != window_id {
+                    return Ok(None);
+                }
+                let (x, y) = (f64::from(event.event_x()), f64::from(event.event_y()));
+                let hit = layout::hit_test(&self.contents, x, y);
+                Ok(hit.map(|(idx, index, text)| {
+                    let mouse_event = MouseEvent {
+                        kind,
+                        button: MouseButton::from(event.detail()),
+                        modifiers: Modifiers::from_state(event.state()),
+                        index,
+                        x: x - text.x,
+                        y: y - text.y,
+                        root_x: f64::from(event.root_x()),
+                        root_y: f64::from(event.root_y()),
+                        width: text.width,
+                        height: text.height,
+                    };
+                    (idx, mouse_event)
+                }))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+impl<B: Backend> Bar<B> {
+    // Creates a new `Bar` drawn on `backend`, placed in `area`.
+    //
+    // Nothing is drawn until content is added.
+    pub fn with_backend(backend: B, position: Position, area: Rect) -> Result<Bar<B>> {
+        // The backend starts out 1px across, see `Bar::new()`.
+        let rect = window_rect(&position, area, 1);
+        Ok(Bar {
             position,
+            backend,
+            buffer: create_image_surface(rect.width, rect.height)?,
+            rect,
+            area,
+            fixed_breadth: None,
             contents: Vec::new(),
-        };
-        bar.set_ewmh_properties();
-
-        // XXX We can't map the window until we've updated the window size, or nothing
-        // gets rendered. I can't tell if this is something we're doing, something Cairo
-        // is doing or something QTile is doing. This'll do for now and we'll see what
-        // it is like with Lanta!
-        // bar.map_window();
-        bar.flush();
+            embedded: Vec::new(),
+        })
+    }
 
-        Ok(bar)
+    // Returns the backend the bar is drawn on.
+    pub fn backend(&self) -> &B {
+        &self.backend
     }
 
     fn flush(&self) {
-        self.conn.flush();
+        self.backend.flush();
     }
 
-    fn map_window(&self) {
-        xcb::map_window(&self.conn, self.window_id);
+    fn orientation(&self) -> Orientation {
+        self.position.orientation()
     }
 
-    fn set_ewmh_properties(&self) {
-        ewmh::set_wm_window_type(
-            &self.conn,
-            self.window_id,
-            &[self.conn.WM_WINDOW_TYPE_DOCK()],
-        );
-
-        // TODO: Update _WM_STRUT_PARTIAL if the height/position of the bar changes?
-        let mut strut_partial = ewmh::StrutPartial {
-            left: 0,
-            right: 0,
-            top: 0,
-            bottom: 0,
-            left_start_y: 0,
-            left_end_y: 0,
-            right_start_y: 0,
-            right_end_y: 0,
-            top_start_x: 0,
-            top_end_x: 0,
-            bottom_start_x: 0,
-            bottom_end_x: 0,
-        };
-        match self.position {
-            Position::Top => strut_partial.top = u32::from(self.height),
-            Position::Bottom => strut_partial.bottom = u32::from(self.height),
+    // The size of the bar along its edge of the area.
+    fn length(&self) -> f64 {
+        match self.orientation() {
+            Orientation::Horizontal => f64::from(self.rect.width),
+            Orientation::Vertical => f64::from(self.rect.height),
         }
-        ewmh::set_wm_strut_partial(&self.conn, self.window_id, strut_partial);
     }
 
-    fn screen(&self) -> Result<xcb::Screen<'_>> {
-        let screen = self
-            .conn
-            .get_setup()
-            .roots()
-            .nth(self.screen_idx)
-            .ok_or_else(|| anyhow!("Invalid screen"))?;
-        Ok(screen)
+    // The size of the bar across, i.e. its height for a horizontal bar.
+    fn breadth(&self) -> u16 {
+        match self.orientation() {
+            Orientation::Horizontal => self.rect.height,
+            Orientation::Vertical => self.rect.width,
+        }
     }
 
-    fn update_bar_height(&mut self, height: u16) -> Result<()> {
-        if self.height != height {
-            self.height = height;
-
-            // If we're at the bottom of the screen, we'll need to update the
-            // position of the window.
-            let y = match self.position {
-                Position::Top => self.offset.y.max(0) as u16,
-                Position::Bottom => {
-                    let h = (self.screen()?.height_in_pixels() - self.height) as i32;
-                    h.checked_add(self.offset.y as i32).unwrap_or(h).max(0) as u16
-                }
-            };
-
-            // Update the height/position of the XCB window and the height of the Cairo surface.
-            let values = [
-                (xcb::CONFIG_WINDOW_Y as u16, u32::from(y)),
-                (xcb::CONFIG_WINDOW_HEIGHT as u16, u32::from(self.height)),
-                (xcb::CONFIG_WINDOW_STACK_MODE as u16, xcb::STACK_MODE_ABOVE),
-            ];
-            xcb::configure_window(&self.conn, self.window_id, &values);
-            self.map_window();
-            self.surface
-                .set_size(i32::from(self.width), i32::from(self.height))
-                .unwrap();
-
-            // Update EWMH properties - we might need to reserve more or less space.
-            self.set_ewmh_properties();
+    fn update_bar_breadth(&mut self, breadth: u16) -> Result<()> {
+        // X doesn't allow zero-sized windows, which we'd otherwise ask for
+        // whenever no widget has any content.
+        let breadth = breadth.max(1);
+        if self.breadth() != breadth {
+            self.rect = window_rect(&self.position, self.area, breadth);
+            self.configure_window()?;
         }
 
         Ok(())
     }
 
-    // Returns the connection to the X server.
+    // Shows or hides the bar. A hidden bar doesn't reserve any space at the
+    // edge of the screen, but is otherwise kept up to date.
+    pub fn set_visible(&mut self, visible: bool) -> Result<()> {
+        self.backend.set_visible(visible)?;
+        self.flush();
+        Ok(())
+    }
+
+    // Updates the space the bar reserves at the edge of the screen after the
+    // screen has changed size, even if the bar itself hasn't moved.
+    pub fn screen_changed(&mut self) -> Result<()> {
+        self.backend.screen_changed()?;
+        self.flush();
+        Ok(())
+    }
+
+    // Fixes the size of the bar across (i.e. its height, for a horizontal
+    // bar), or lets it fit the texts if `None`. Texts that don't fit are cut
+    // off.
     //
-    // The owner of the `Bar` is responsible for polling this for events,
-    // passing each to `Bar::process_event()`.
-    pub fn connection(&self) -> &Rc<ewmh::Connection> {
-        &self.conn
+    // This takes effect the next time the bar is laid out, e.g. when content
+    // is added or the bar is reset.
+    pub fn set_fixed_breadth(&mut self, breadth: Option<u16>) {
+        self.fixed_breadth = breadth;
     }
 
-    // Process an X event received from the `Bar::connection()`.
-    pub fn process_event(&mut self, event: xcb::GenericEvent) -> Result<()> {
-        let expose = event.response_type() & !0x80 == xcb::EXPOSE;
-        if expose {
-            println!("Redrawing entire bar - expose event.");
+    // Removes all widgets' content and moves the bar to `position`, ready for
+    // a new set of widgets to be added in the given zones. The window is kept.
+    pub fn reset(&mut self, position: Position, alignments: &[Alignment]) -> Result<()> {
+        self.position = position;
+        self.contents = alignments
+            .iter()
+            .map(|&alignment| Content {
+                alignment,
+                texts: Vec::new(),
+                breadth: 0.0,
+            })
+            .collect();
+        self.embedded.clear();
+        self.rect = window_rect(&self.position, self.area, 1);
+        self.configure_window()?;
+        self.redraw_entire_bar()
+    }
+
+    // Moves the bar into a new area of the root window, e.g. because the
+    // monitor it is shown on has changed resolution or has been moved.
+    pub fn set_area(&mut self, area: Rect) -> Result<()> {
+        if self.area != area {
+            self.area = area;
+            self.rect = window_rect(&self.position, area, self.breadth());
+            self.configure_window()?;
             self.redraw_entire_bar()?;
         }
+
+        Ok(())
+    }
+
+    fn configure_window(&mut self) -> Result<()> {
+        self.backend.configure(&self.position, self.rect)?;
+
+        // The back buffer can't be resized, so replace it. Everything is
+        // redrawn after the bar changes size anyway.
+        let size = (i32::from(self.rect.width), i32::from(self.rect.height));
+        if (self.buffer.width(), self.buffer.height()) != size {
+            self.buffer = create_image_surface(self.rect.width, self.rect.height)?;
+        }
+
         Ok(())
     }
 
-    // Add a new widget's content to the `Bar`.
+    // Add a new widget's content to the `Bar`, in the given zone.
     //
     // Returns the index of the widget within the bar, so that subsequent
     // updates can be made by calling `Bar::update_content()`.
-    pub fn add_content(&mut self, content: Vec<Text>) -> Result<usize> {
+    pub fn add_content(&mut self, alignment: Alignment, content: Vec<Text>) -> Result<usize> {
         let idx = self.contents.len();
-        self.contents.push(Vec::new());
+        self.contents.push(Content {
+            alignment,
+            texts: Vec::new(),
+            breadth: 0.0,
+        });
         self.update_content(idx, content)?;
         Ok(idx)
     }
@@ -287,118 +385,120 @@ impl Bar {
     pub fn update_content(&mut self, idx: usize, content: Vec<Text>) -> Result<()> {
         // If the text is the same, don't bother re-computing the text or
         // redrawing it. This is a spurious wake-up.
-        let old = &self.contents[idx];
+        let old = &self.contents[idx].texts;
         if &content == old {
             return Ok(());
         }
 
-        let mut new = content
+        let new = content
             .into_iter()
-            .map(|text| text.compute(&self.surface))
+            .map(|text| text.compute(&self.buffer))
             .collect::<Result<Vec<_>>>()?;
 
-        let error_margin = f64::EPSILON; // Use an epsilon for comparison
-
-        // If there are any new texts or any non-stretch texts changed size,
-        // we'll redraw all texts.
-        let redraw_entire_bar = old.len() != new.len()
-            || old
-                .iter()
-                .zip(&new)
-                .any(|(old, new)| ((old.width - new.width).abs() < error_margin) && !new.stretch);
-
-        // Steal dimenions from old ComputedText. If we need new dimensions,
-        // they'll be recomputed by redraw_entire_bar().
-        for (new, old) in new.iter_mut().zip(old.iter()) {
-            new.x = old.x;
-            new.y = old.y;
-            new.height = old.height;
-            // Only use width for stretch widgets.
-            if new.stretch {
-                new.width = old.width;
+        let (length, orientation) = (self.length(), self.orientation());
+        let fixed_breadth = self.fixed_breadth.map(f64::from);
+        match layout::update_content(
+            &mut self.contents,
+            idx,
+            new,
+            length,
+            orientation,
+            fixed_breadth,
+        ) {
+            Redraw::Content => {
+                trace!("Redrawing widget {idx}");
+                self.redraw_content(idx)?;
+            }
+            Redraw::Bar { breadth } => {
+                trace!("Redrawing entire bar after widget {idx} changed size");
+                self.update_bar_breadth(breadth as u16)?;
+                self.redraw_all()?;
             }
-        }
-
-        self.contents[idx] = new;
-
-        if !redraw_entire_bar {
-            println!("Redrawing one");
-            self.redraw_content(idx)?;
-        } else {
-            println!("Redrawing entire bar - widget update");
-            self.redraw_entire_bar()?;
         }
 
         Ok(())
     }
 
     fn redraw_content(&mut self, idx: usize) -> Result<()> {
-        for text in &mut self.contents[idx] {
-            text.render(&self.surface)?;
+        let texts = &self.contents[idx].texts;
+        for text in texts {
+            text.render(&self.buffer)?;
         }
 
-        self.flush();
+        // Only copy the area covered by the widget's texts, which are next
+        // to each other (or on top of each other on a vertical bar).
+        if let (Some(first), Some(last)) = (texts.first(), texts.last()) {
+            let (x, y) = (first.x, first.y);
+            self.present(x, y, last.x + last.width - x, last.y + last.height - y)?;
+        }
 
         Ok(())
     }
 
     pub fn redraw_entire_bar(&mut self) -> Result<()> {
-        self.recompute_dimensions()?;
+        let (length, orientation) = (self.length(), self.orientation());
+        let fixed_breadth = self.fixed_breadth.map(f64::from);
+        let breadth = layout::layout(&mut self.contents, length, orientation, fixed_breadth);
+        self.update_bar_breadth(breadth as u16)?;
+        self.redraw_all()
+    }
+
+    fn redraw_all(&mut self) -> Result<()> {
+        // Start from a transparent buffer, so that nothing is left behind
+        // where texts have moved away from.
+        let context = cairo::Context::new(&self.buffer)?;
+        context.set_operator(cairo::Operator::Clear);
+        context.paint()?;
 
-        for idx in 0..self.contents.len() {
-            self.redraw_content(idx)?;
+        for text in self.contents.iter().flat_map(|content| &content.texts) {
+            text.render(&self.buffer)?;
         }
-        Ok(())
+        self.place_embedded_windows();
+
+        let (width, height) = (f64::from(self.rect.width), f64::from(self.rect.height));
+        self.present(0.0, 0.0, width, height)
     }
 
-    fn recompute_dimensions(&mut self) -> Result<()> {
-        // Set the height to the max height of any content.
-        let height = self
-            .contents
-            .iter()
-            .flatten()
-            .map(|text| text.height)
-            .max_by_key(|height| OrderedFloat(*height))
-            .unwrap_or(0.0);
-        for text in self.contents.iter_mut().flatten() {
-            text.height = height;
-        }
-        self.update_bar_height(height as u16)?;
+    // Embeds `window` over the first text of the widget at `idx`, keeping it
+    // there as the bar is laid out again. This replaces any window previously
+    // embedded for the widget.
+    pub fn embed_window(&mut self, idx: usize, window: xcb::Window) {
+        self.embedded.retain(|(other, _)| *other != idx);
+        self.embedded.push((idx, window));
+        self.place_embedded_windows();
+    }
 
-        // Sum the width of all non-stretch texts. Subtract from the screen
-        // width to get width remaining for stretch texts.
-        let used: f64 = self
-            .contents
-            .iter()
-            .flatten()
-            .filter(|text| !text.stretch)
-            .map(|text| text.width)
-            .sum();
-        let remaining = f64::from(self.width) - used;
-
-        // Distribute remaining width evenly between stretch texts.
-        let stretches_count = self
-            .contents
-            .iter()
-            .flatten()
-            .filter(|text| text.stretch)
-            .count();
-        let stretch_width = remaining / (stretches_count as f64);
-        let stretches = self
-            .contents
-            .iter_mut()
-            .flatten()
-            .filter(|text| text.stretch);
-        for text in stretches {
-            text.width = stretch_width;
-        }
+    // Stops placing the window embedded for the widget at `idx`, e.g.
+    // because the widget (and its window) has gone.
+    pub fn remove_window(&mut self, idx: usize) {
+        self.embedded.retain(|(other, _)| *other != idx);
+    }
 
-        // Set x based on computed widths.
-        let mut x = 0.0;
-        for text in self.contents.iter_mut().flatten() {
-            text.x = x;
-            x += text.width;
+    fn place_embedded_windows(&mut self) {
+        for &(idx, window) in &self.embedded {
+            // Widgets without any text yet have nowhere to put their window.
+            if let Some(text) = self.contents.get(idx).and_then(|c| c.texts.first()) {
+                let rect = Rect {
+                    x: text.x as i16,
+                    y: text.y as i16,
+                    width: text.width as u16,
+                    height: text.height as u16,
+                };
+                self.backend.embed_window(window, rect);
+            }
         }
+        self.flush();
+    }
+
+    // Copies the given area of the back buffer onto the backend's surface.
+    fn present(&self, x: f64, y: f64, width: f64, height: f64) -> Result<()> {
+        let context = cairo::Context::new(self.backend.surface())?;
+        context.set_operator(cairo::Operator::Source);
+        context.set_source_surface(&self.buffer, 0.0, 0.0)?;
+        context.rectangle(x, y, width, height);
+        context.fill()?;
+
+        self.flush();
 
         Ok(())
     }
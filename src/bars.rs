@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use anyhow::{anyhow, Context, Result};
+use log::warn;
+use xcb_util::ewmh;
+
+use crate::bar::{Alignment, Bar, Offset, Position, Rect};
+use crate::mouse::MouseEvent;
+use crate::output::Output;
+use crate::randr::{self, Monitors};
+use crate::text::Text;
+use crate::widget::ErrorDisplay;
+use crate::xcb::describe_error;
+
+// Where the bars of a `Cnx` instance are placed.
+pub(crate) enum Placement {
+    // A single bar spanning the screen, or the given width of it.
+    Single { width: Option<u16>, offset: Offset },
+    // One bar on each of the selected RandR monitors.
+    Monitors(Monitors),
+}
+
+// The options shared by all the bars of a `Cnx` instance.
+pub(crate) struct BarOptions {
+    pub position: Position,
+    pub placement: Placement,
+    // The name that window manager and compositor rules can match the bars'
+    // windows by.
+    pub name: String,
+    // The height of a horizontal bar, or the width of a vertical one, if
+    // it shouldn't fit the tallest (or widest) text.
+    pub height: Option<u16>,
+    // What to show in place of a widget that has failed, if anything.
+    pub error_display: Option<ErrorDisplay>,
+}
+
+// The set of `Bar`s shown by a `Cnx` instance.
+//
+// Every bar shows the same widgets. The most recent content of each widget is
+// kept, so that bars created for newly connected monitors can be populated
+// straight away.
+pub(crate) struct Bars {
+    conn: Rc<ewmh::Connection>,
+    screen_idx: usize,
+    options: BarOptions,
+    // The first event code of the RandR extension, if we're listening for
+    // screen and monitor changes.
+    randr_first_event: Option<u8>,
+
+    alignments: Vec<Alignment>,
+    contents: Vec<Vec<Text>>,
+    // The windows of widgets to embed in the bar, keyed by widget index.
+    // A window can only be shown once, so it is embedded in the first bar.
+    windows: HashMap<usize, xcb::Window>,
+
+    // Each bar, along with the name of the monitor it is shown on. (The name
+    // is empty for `Placement::Single`).
+    bars: Vec<(String, Bar)>,
+    // Whether the bars are shown, see `Bars::set_visible()`.
+    visible: bool,
+}
+
+impl Bars {
+    pub fn new(
+        conn: Rc<ewmh::Connection>,
+        screen_idx: usize,
+        options: BarOptions,
+        alignments: Vec<Alignment>,
+    ) -> Result<Bars> {
+        let mut bars = Bars {
+            conn,
+            screen_idx,
+            options,
+            randr_first_event: None,
+            contents: vec![Vec::new(); alignments.len()],
+            alignments,
+            windows: HashMap::new(),
+            bars: Vec::new(),
+            visible: true,
+        };
+
+        bars.select_randr_input()?;
+        bars.update_monitors()?;
+
+        Ok(bars)
+    }
+
+    // Moves the bars to a new connection, after the previous one was lost.
+    //
+    // The bars' windows went with the old connection, so new ones are created
+    // and populated with the most recent content of each widget. Embedded
+    // windows belong to the widgets' own (also lost) connections, so they are
+    // dropped.
+    pub fn reconnect(&mut self, conn: Rc<ewmh::Connection>, screen_idx: usize) -> Result<()> {
+        self.bars.clear();
+        self.windows.clear();
+        self.conn = conn;
+        self.screen_idx = screen_idx;
+        self.randr_first_event = None;
+        self.select_randr_input()?;
+        self.update_monitors()
+    }
+
+    // Listens for RandR events, so that the bars can follow the monitors
+    // they're on and keep their struts up to date as the screen changes size.
+    //
+    // Bars on monitors can't be placed without RandR, but a single bar can
+    // do without it, apart from its strut going stale.
+    fn select_randr_input(&mut self) -> Result<()> {
+        if self.randr_first_event.is_some() {
+            return Ok(());
+        }
+        match randr::select_input(&self.conn, self.screen()?.root()) {
+            Ok(first_event) => self.randr_first_event = Some(first_event),
+            Err(err) => match self.options.placement {
+                Placement::Monitors(_) => return Err(err),
+                Placement::Single { .. } => {
+                    warn!("{err:#}, the bar won't follow changes to the screen");
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn screen(&self) -> Result<xcb::Screen<'_>> {
+        let screen = self
+            .conn
+            .get_setup()
+            .roots()
+            .nth(self.screen_idx)
+            .ok_or_else(|| anyhow!("Invalid screen"))?;
+        Ok(screen)
+    }
+
+    // Returns the area that each bar should be placed in, along with the name
+    // of the monitor it is on.
+    fn areas(&self) -> Result<Vec<(String, Rect)>> {
+        let screen = self.screen()?;
+        match &self.options.placement {
+            Placement::Single { width, offset } => {
+                // The screen's size in the connection setup is never updated,
+                // so ask for the root window's current size, as the strut does.
+                let root = xcb::get_geometry(&self.conn, screen.root())
+                    .get_reply()
+                    .context("Failed to get root window geometry")?;
+                let area = single_area(*width, *offset, root.width(), root.height());
+                Ok(vec![(String::new(), area)])
+            }
+            Placement::Monitors(monitors) => {
+                let monitors = randr::select_monitors(&self.conn, screen.root(), monitors)?;
+                Ok(monitors.into_iter().map(|m| (m.name, m.area)).collect())
+            }
+        }
+    }
+
+    // Creates, moves and destroys bars so that there is exactly one bar for
+    // each selected monitor.
+    fn update_monitors(&mut self) -> Result<()> {
+        let areas = self.areas()?;
+
+        // Dropping a `Bar` destroys its window.
+        self.bars
+            .retain(|(name, _)| areas.iter().any(|(monitor, _)| monitor == name));
+
+        for (name, area) in areas {
+            match self.bars.iter_mut().find(|(monitor, _)| *monitor == name) {
+                Some((_, bar)) => bar.set_area(area)?,
+                None => {
+                    let bar = self.create_bar(&name, area)?;
+                    self.bars.push((name, bar));
+                }
+            }
+        }
+
+        // The first bar may be new, or may have been reset.
+        if let Some((_, bar)) = self.bars.first_mut() {
+            for (&idx, &window) in &self.windows {
+                bar.embed_window(idx, window);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Creates a bar in `area` of the named monitor.
+    //
+    // Each bar's window gets an instance name of its own, made from our name
+    // and the name of its monitor (if any), so that rules can target the bar
+    // on a particular monitor.
+    fn create_bar(&self, monitor: &str, area: Rect) -> Result<Bar> {
+        let instance = if monitor.is_empty() {
+            self.options.name.clone()
+        } else {
+            format!("{}-{}", self.options.name, monitor)
+        };
+        let mut bar = Bar::new(
+            self.conn.clone(),
+            self.screen_idx,
+            self.options.position.clone(),
+            area,
+            &instance,
+        )?;
+        bar.set_fixed_breadth(self.options.height);
+        bar.set_visible(self.visible)?;
+        for (alignment, content) in self.alignments.iter().zip(&self.contents) {
+            bar.add_content(*alignment, content.clone())?;
+        }
+        Ok(bar)
+    }
+
+    // Replaces (or removes) the window embedded for the widget at `idx`, e.g.
+    // because the widget has been restarted.
+    pub fn set_window(&mut self, idx: usize, window: Option<xcb::Window>) {
+        match window {
+            Some(window) => self.windows.insert(idx, window),
+            None => self.windows.remove(&idx),
+        };
+        if let Some((_, bar)) = self.bars.first_mut() {
+            match window {
+                Some(window) => bar.embed_window(idx, window),
+                None => bar.remove_window(idx),
+            }
+        }
+    }
+
+    // Process an X event, passing it on to the `Bar` it is for.
+    //
+    // Returns the index of the widget and the mouse event, if the event was a
+    // mouse event on one of the widgets' texts.
+    pub fn process_event(
+        &mut self,
+        event: xcb::GenericEvent,
+    ) -> Result<Option<(usize, MouseEvent)>> {
+        if let Some(error) = describe_error(&event) {
+            warn!("{error}");
+            return Ok(None);
+        }
+
+        if let Some(first_event) = self.randr_first_event {
+            if randr::is_change_event(&event, first_event) {
+                self.update_monitors()?;
+                for (_, bar) in &mut self.bars {
+                    bar.screen_changed()?;
+                }
+                return Ok(None);
+            }
+        }
+
+        for (_, bar) in &mut self.bars {
+            if let Some(mouse_event) = bar.process_event(&event)? {
+                return Ok(Some(mouse_event));
+            }
+        }
+        Ok(None)
+    }
+}
+
+// Returns the area of a single bar on a root window of the given size.
+fn single_area(width: Option<u16>, offset: Offset, root_width: u16, root_height: u16) -> Rect {
+    Rect {
+        x: offset.x,
+        y: offset.y,
+        width: width.unwrap_or(root_width),
+        height: root_height,
+    }
+}
+
+impl Output for Bars {
+    // Replaces the options and widgets of the bars.
+    //
+    // Existing bars (and their windows) are reused for any monitors that are
+    // still selected, and start out empty until the new widgets yield content.
+    // If the name has changed, the bars are recreated instead.
+    //
+    // The windows of the previous widgets have gone with them, and those of
+    // the new widgets are passed to `Bars::set_window()`.
+    fn reconfigure(&mut self, options: BarOptions, alignments: Vec<Alignment>) -> Result<()> {
+        if options.name != self.options.name {
+            self.bars.clear();
+        }
+        self.options = options;
+        self.select_randr_input()?;
+
+        self.contents = vec![Vec::new(); alignments.len()];
+        self.alignments = alignments;
+        self.windows.clear();
+        for (_, bar) in &mut self.bars {
+            bar.set_fixed_breadth(self.options.height);
+            bar.reset(self.options.position.clone(), &self.alignments)?;
+        }
+
+        self.update_monitors()
+    }
+
+    // Shows or hides all of the bars, including any created later on (e.g.
+    // for a newly connected monitor) until this is called again.
+    fn set_visible(&mut self, visible: bool) -> Result<()> {
+        self.visible = visible;
+        for (_, bar) in &mut self.bars {
+            bar.set_visible(visible)?;
+        }
+        Ok(())
+    }
+
+    fn visible(&self) -> bool {
+        self.visible
+    }
+
+    fn content(&self, idx: usize) -> &[Text] {
+        &self.contents[idx]
+    }
+
+    // Updates an existing widget's content in every `Bar`.
+    fn update_content(&mut self, idx: usize, content: Vec<Text>) -> Result<()> {
+        for (_, bar) in &mut self.bars {
+            bar.update_content(idx, content.clone())?;
+        }
+        self.contents[idx] = content;
+        Ok(())
+    }
+
+    fn show_error(&mut self, idx: usize, error: &str) -> Result<()> {
+        match &self.options.error_display {
+            Some(display) => {
+                let text = display.text(error);
+                self.update_content(idx, vec![text])
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::single_area;
+    use crate::bar::{Offset, Rect};
+
+    #[test]
+    fn single_area_follows_root() {
+        let offset = Offset { x: 10, y: 0 };
+        let area = single_area(None, offset, 1920, 1080);
+        assert_eq!((area.x, area.width, area.height), (10, 1920, 1080));
+
+        // After the screen has been resized.
+        let area = single_area(None, offset, 2560, 1440);
+        assert_eq!(
+            area,
+            Rect {
+                x: 10,
+                y: 0,
+                width: 2560,
+                height: 1440,
+            }
+        );
+        let area = single_area(Some(800), offset, 2560, 1440);
+        assert_eq!((area.width, area.height), (800, 1440));
+    }
+}
@@ -1,12 +1,11 @@
 use anyhow::{anyhow, Context, Error, Result};
+use crate::schedule;
 use crate::text::{Attributes, Color, Text};
 use crate::widget::{Widget, WidgetStream};
 use std::fs::File;
 use std::io::Read;
 use std::str::FromStr;
 use std::time::Duration;
-use tokio::time;
-use tokio_stream::wrappers::IntervalStream;
 use tokio_stream::StreamExt;
 
 /// Represent Battery's operating status
@@ -90,6 +89,7 @@ impl Battery {
     ///     fg_color: Color::white(),
     ///     bg_color: None,
     ///     padding: Padding::new(8.0, 8.0, 0.0, 0.0),
+    ///     valign: VerticalAlignment::Center,
     /// };
     ///
     /// let mut cnx = Cnx::new(Position::Top);
@@ -113,6 +113,15 @@ impl Battery {
         }
     }
 
+    /// Sets how often the battery's status is updated. The default is a
+    /// minute.
+    pub fn with_interval(self, update_interval: Duration) -> Self {
+        Self {
+            update_interval,
+            ..self
+        }
+    }
+
     fn load_value_inner<T>(&self, file: &str) -> Result<T>
     where
         T: FromStr,
@@ -172,8 +181,8 @@ impl Battery {
 
 impl Widget for Battery {
     fn into_stream(self: Box<Self>) -> Result<WidgetStream> {
-        let interval = time::interval(self.update_interval);
-        let stream = IntervalStream::new(interval).map(move |_| self.tick());
+        let ticks = schedule::ticks(self.update_interval);
+        let stream = ticks.map(move |_| self.tick());
 
         Ok(Box::pin(stream))
     }
@@ -1,9 +1,8 @@
 use anyhow::Result;
 use std::time::Duration;
-use tokio::time;
-use tokio_stream::wrappers::IntervalStream;
 use tokio_stream::StreamExt;
 
+use crate::schedule;
 use crate::text::{Attributes, Text};
 use crate::widget::{Widget, WidgetStream};
 
@@ -14,12 +13,29 @@ use crate::widget::{Widget, WidgetStream};
 pub struct Clock {
     attr: Attributes,
     format_str: Option<String>,
+    update_interval: Duration,
 }
 
 impl Clock {
     // Creates a new Clock widget.
     pub fn new(attr: Attributes, format_str: Option<String>) -> Self {
-        Self { attr, format_str }
+        Self {
+            attr,
+            format_str,
+            update_interval: Duration::from_secs(60),
+        }
+    }
+
+    /// Sets how often the time is updated. The default is a minute, which is
+    /// enough unless the format includes seconds.
+    ///
+    /// Updates happen when the time is a multiple of the interval, e.g. on
+    /// the minute, so the time shown is never behind.
+    pub fn with_interval(self, update_interval: Duration) -> Self {
+        Self {
+            update_interval,
+            ..self
+        }
     }
 
     fn tick(&self) -> Vec<Text> {
@@ -41,11 +57,10 @@ impl Clock {
 
 impl Widget for Clock {
     fn into_stream(self: Box<Self>) -> Result<WidgetStream> {
-        // As we're not showing seconds, we can sleep for however long
-        // it takes until the minutes changes between updates.
-        let one_minute = Duration::from_secs(60);
-        let interval = time::interval(one_minute);
-        let stream = IntervalStream::new(interval).map(move |_| Ok(self.tick()));
+        // Ticks are aligned to the wall clock, so the time shown changes
+        // as soon as the minute does.
+        let ticks = schedule::ticks(self.update_interval);
+        let stream = ticks.map(move |_| Ok(self.tick()));
 
         Ok(Box::pin(stream))
     }
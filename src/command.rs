@@ -1,16 +1,23 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use crate::process::{self, Timeout};
+use crate::schedule;
 use crate::text::{Attributes, Text};
 use crate::widget::{Widget, WidgetStream};
-use std::process::Command as Process;
+use futures::StreamExt;
+use std::rc::Rc;
 use std::time::Duration;
-use tokio::time;
-use tokio_stream::wrappers::IntervalStream;
-use tokio_stream::StreamExt;
+use tokio::process::Command as Process;
 
+/// Shows the output of a shell command, which is run periodically.
+///
+/// The command is run with `sh -c`, without blocking the rest of the bar. If
+/// it fails, exits unsuccessfully or runs for longer than its timeout, the
+/// widget yields an error.
 pub struct Command {
     attr: Attributes,
     command: String,
     update_interval: Duration,
+    timeout: Timeout,
 }
 
 impl Command {
@@ -43,6 +50,7 @@ impl Command {
     ///     fg_color: Color::white(),
     ///     bg_color: None,
     ///     padding: Padding::new(8.0, 8.0, 0.0, 0.0),
+    ///     valign: VerticalAlignment::Center,
     /// };
     ///
     /// let mut cnx = Cnx::new(Position::Top);
@@ -56,31 +64,58 @@ impl Command {
             attr,
             command,
             update_interval,
+            timeout: Timeout::default(),
         }
     }
 
-    fn tick(&self) -> Vec<Text> {
-        let output = Process::new("sh")
-            .arg("-c")
-            .arg(self.command.clone())
-            .output()
-            .expect("failed to execute process");
+    /// Sets how long the command may run for before the widget gives up on
+    /// it. The default is ten seconds.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        let timeout = Timeout {
+            duration: timeout,
+            ..self.timeout
+        };
+        Self { timeout, ..self }
+    }
+
+    /// Sets whether a command that has timed out is killed (the default), or
+    /// left running in the background.
+    pub fn with_kill_on_timeout(self, kill: bool) -> Self {
+        let timeout = Timeout {
+            kill,
+            ..self.timeout
+        };
+        Self { timeout, ..self }
+    }
+
+    async fn tick(&self) -> Result<Vec<Text>> {
+        let mut command = Process::new("sh");
+        command.arg("-c").arg(&self.command);
+        let output = process::output(&mut command, self.timeout)
+            .await
+            .with_context(|| format!("Failed to run `{}`", self.command))?;
 
         let texts = vec![Text {
             attr: self.attr.clone(),
-            text: String::from_utf8(output.stdout).unwrap_or_else(|_| "error".into()),
+            text: output,
             stretch: false,
             markup: true,
         }];
 
-        texts
+        Ok(texts)
     }
 }
 
 impl Widget for Command {
     fn into_stream(self: Box<Self>) -> Result<WidgetStream> {
-        let interval = time::interval(self.update_interval);
-        let stream = IntervalStream::new(interval).map(move |_| Ok(self.tick()));
+        // A command that runs for longer than the interval skips the runs
+        // it overlaps with, rather than them all happening at once.
+        let ticks = schedule::ticks(self.update_interval);
+        let widget: Rc<Command> = Rc::from(self);
+        let stream = ticks.then(move |_| {
+            let widget = widget.clone();
+            async move { widget.tick().await }
+        });
 
         Ok(Box::pin(stream))
     }
@@ -0,0 +1,739 @@
+//! Declarative configuration of a [`Cnx`] instance.
+//!
+//! The configuration is read from a TOML file, by default
+//! `$XDG_CONFIG_HOME/rusty-bar/config.toml`. See `config.example.toml` in the
+//! repository for a complete example.
+//!
+//! Widgets which take a `format` option substitute placeholders such as
+//! `{usage}` into the format string. The result is rendered as Pango markup.
+//!
+//! [`Cnx`]: ../widget/struct.Cnx.html
+
+use anyhow::{anyhow, Context, Result};
+use byte_unit::{Byte, ByteUnit};
+use serde_derive::Deserialize;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::active_window_title::ActiveWindowTitle;
+use crate::bar::{Alignment, Position};
+use crate::battery::{Battery, BatteryInfo, Status};
+use crate::clock::Clock;
+use crate::command::Command;
+use crate::cpu::Cpu;
+use crate::disk_usage::{DiskInfo, DiskUsage};
+use crate::i3bar::I3Bar;
+use crate::ipc_text::IpcText;
+use crate::leftwm::{LeftWM, LeftWMAttributes};
+use crate::randr::Monitors;
+use crate::sensors::Sensors;
+use crate::text::{Attributes, Color, Font, Padding, Threshold, VerticalAlignment};
+use crate::tray::Tray;
+use crate::volume::Volume;
+use crate::widget::{Cnx, ErrorDisplay, RestartPolicy, Widget};
+use crate::wireless::Wireless;
+
+/// The configuration used when no configuration file exists.
+pub const DEFAULT_CONFIG: &str = include_str!("../config.example.toml");
+
+/// Returns the default path of the configuration file.
+///
+/// This is `$XDG_CONFIG_HOME/rusty-bar/config.toml`, falling back to
+/// `$HOME/.config/rusty-bar/config.toml`.
+pub fn default_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_home.join("rusty-bar").join("config.toml"))
+}
+
+/// The top-level configuration of the bar.
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    position: PositionConfig,
+    width: Option<u16>,
+    height: Option<u16>,
+    #[serde(default)]
+    offset: OffsetConfig,
+    monitors: Option<MonitorsConfig>,
+    name: Option<String>,
+    #[serde(default)]
+    reconnect: bool,
+    /// How widgets that have failed are shown.
+    #[serde(default)]
+    errors: ErrorsConfig,
+    /// The default attributes of every widget.
+    #[serde(default)]
+    attributes: AttributesConfig,
+    /// The widgets, in the order they are added to the bar.
+    #[serde(default)]
+    widgets: Vec<WidgetConfig>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum PositionConfig {
+    #[default]
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct OffsetConfig {
+    #[serde(default)]
+    x: i16,
+    #[serde(default)]
+    y: i16,
+}
+
+// Either `monitors = "all"` or `monitors = ["eDP-1", "HDMI-1"]`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum MonitorsConfig {
+    All(AllMonitors),
+    Named(Vec<String>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum AllMonitors {
+    All,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorsConfig {
+    /// Whether to show failed widgets on the bar at all.
+    #[serde(default = "default_show_errors")]
+    show: bool,
+    /// Placeholders: `{error}`.
+    #[serde(default = "default_error_format")]
+    format: String,
+    /// Applied over the default attributes, with the text red unless set.
+    #[serde(default)]
+    attributes: AttributesConfig,
+}
+
+impl Default for ErrorsConfig {
+    fn default() -> ErrorsConfig {
+        ErrorsConfig {
+            show: default_show_errors(),
+            format: default_error_format(),
+            attributes: AttributesConfig::default(),
+        }
+    }
+}
+
+fn default_show_errors() -> bool {
+    true
+}
+
+fn default_error_format() -> String {
+    "!".to_owned()
+}
+
+fn default_kill_on_timeout() -> bool {
+    true
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum AlignmentConfig {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// Attributes of a widget's text. Any attributes that are not set are
+/// inherited from the bar's default attributes.
+#[derive(Clone, Debug, Default, Deserialize)]
+struct AttributesConfig {
+    font: Option<String>,
+    fg_color: Option<String>,
+    bg_color: Option<String>,
+    /// Padding as `[left, right, top, bottom]`.
+    padding: Option<[f64; 4]>,
+    valign: Option<VerticalAlignmentConfig>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum VerticalAlignmentConfig {
+    Top,
+    Center,
+    Bottom,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum RestartConfig {
+    Never,
+    OnFailure,
+    #[default]
+    Always,
+}
+
+#[derive(Debug, Deserialize)]
+struct WidgetConfig {
+    #[serde(default)]
+    align: AlignmentConfig,
+    #[serde(default)]
+    restart: RestartConfig,
+    /// Refreshes the widget on SIGRTMIN+signal.
+    signal: Option<u32>,
+    #[serde(default)]
+    attributes: AttributesConfig,
+    #[serde(flatten)]
+    kind: WidgetKind,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum WidgetKind {
+    ActiveWindowTitle,
+    Battery {
+        battery: Option<String>,
+        warning_color: Option<String>,
+        /// Placeholders: `{icon}`, `{capacity}`, `{status}`.
+        format: Option<String>,
+        #[serde(default)]
+        icons: BatteryIcons,
+        /// Seconds between updates, by default 60.
+        interval: Option<u64>,
+    },
+    Clock {
+        /// A `chrono` format string.
+        format: Option<String>,
+        /// Seconds between updates, by default 60.
+        interval: Option<u64>,
+    },
+    Command {
+        command: String,
+        /// Seconds between runs of the command.
+        interval: u64,
+        /// Seconds the command may run for, by default 10.
+        timeout: Option<u64>,
+        /// Whether to kill the command once it has timed out, rather than
+        /// leave it running.
+        #[serde(default = "default_kill_on_timeout")]
+        kill_on_timeout: bool,
+    },
+    Cpu {
+        /// Placeholders: `{usage}`.
+        format: Option<String>,
+        /// Seconds between updates, by default 10.
+        interval: Option<u64>,
+    },
+    DiskUsage {
+        path: String,
+        /// Placeholders: `{used_percent}`, `{used}`, `{free}`, `{total}`.
+        format: Option<String>,
+        /// Seconds between updates, by default 3600.
+        interval: Option<u64>,
+    },
+    #[serde(rename = "i3bar")]
+    I3Bar {
+        /// A program speaking the i3bar protocol, run with `sh -c`.
+        command: String,
+        /// The text shown between blocks, by default `|`.
+        separator: Option<String>,
+    },
+    IpcText {
+        /// The name that text is sent to, with `rusty-bar msg set <name>`.
+        name: String,
+        /// The text shown until some is sent.
+        text: Option<String>,
+    },
+    #[serde(rename = "leftwm")]
+    LeftWM {
+        output: String,
+        #[serde(default)]
+        focused: AttributesConfig,
+        #[serde(default)]
+        visible: AttributesConfig,
+        #[serde(default)]
+        busy: AttributesConfig,
+        #[serde(default)]
+        empty: AttributesConfig,
+    },
+    Sensors {
+        sensors: Vec<String>,
+        /// Seconds between updates, by default 60.
+        interval: Option<u64>,
+        /// Seconds `sensors` may run for, by default 10.
+        timeout: Option<u64>,
+        /// Whether to kill `sensors` once it has timed out, rather than
+        /// leave it running.
+        #[serde(default = "default_kill_on_timeout")]
+        kill_on_timeout: bool,
+    },
+    Tray,
+    Volume,
+    Wireless {
+        interface: String,
+        /// Whether to color the signal quality using the default thresholds.
+        #[serde(default)]
+        threshold: bool,
+        /// Seconds between updates, by default 3600.
+        interval: Option<u64>,
+    },
+}
+
+/// The text substituted for `{icon}` in a battery's `format`.
+#[derive(Clone, Debug, Default, Deserialize)]
+struct BatteryIcons {
+    #[serde(default)]
+    full: String,
+    #[serde(default)]
+    charging: String,
+    #[serde(default)]
+    discharging: String,
+    #[serde(default)]
+    unknown: String,
+}
+
+impl Config {
+    /// Reads and parses the configuration file at `path`.
+    pub fn load(path: &Path) -> Result<Config> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+        Config::parse(&contents)
+            .with_context(|| format!("Failed to parse config file: {}", path.display()))
+    }
+
+    /// Parses a configuration from the contents of a TOML file.
+    pub fn parse(contents: &str) -> Result<Config> {
+        let config = toml::from_str(contents)?;
+        Ok(config)
+    }
+
+    /// Builds a [`Cnx`] instance with the configured position and widgets.
+    ///
+    /// [`Cnx`]: ../widget/struct.Cnx.html
+    pub fn into_cnx(self) -> Result<Cnx> {
+        let position = match self.position {
+            PositionConfig::Top => Position::Top,
+            PositionConfig::Bottom => Position::Bottom,
+            PositionConfig::Left => Position::Left,
+            PositionConfig::Right => Position::Right,
+        };
+        let mut cnx = Cnx::new(position.clone())
+            .with_width(self.width)
+            .with_height(self.height)
+            .with_reconnect(self.reconnect)
+            .with_offset(self.offset.x, self.offset.y);
+        match self.monitors {
+            Some(MonitorsConfig::All(AllMonitors::All)) => {
+                cnx = cnx.with_monitors(Monitors::All);
+            }
+            Some(MonitorsConfig::Named(names)) => {
+                cnx = cnx.with_monitors(Monitors::Named(names));
+            }
+            None => {}
+        }
+        if let Some(name) = self.name {
+            cnx = cnx.with_name(name);
+        }
+
+        let default_attr = self
+            .attributes
+            .apply(&base_attributes())
+            .context("Invalid default attributes")?;
+        if self.errors.show {
+            let attr = Attributes {
+                fg_color: Color::red(),
+                ..default_attr.clone()
+            };
+            let error_display = ErrorDisplay {
+                attr: self
+                    .errors
+                    .attributes
+                    .apply(&attr)
+                    .context("Invalid error attributes")?,
+                format: self.errors.format,
+            };
+            cnx = cnx.with_error_display(Some(error_display));
+        }
+
+        for (idx, widget) in self.widgets.into_iter().enumerate() {
+            let attr = widget
+                .attributes
+                .apply(&default_attr)
+                .with_context(|| format!("Invalid attributes for widget {idx}"))?;
+            let alignment = match widget.align {
+                AlignmentConfig::Left => Alignment::Left,
+                AlignmentConfig::Center => Alignment::Center,
+                AlignmentConfig::Right => Alignment::Right,
+            };
+            let restart_policy = match widget.restart {
+                RestartConfig::Never => RestartPolicy::Never,
+                RestartConfig::OnFailure => RestartPolicy::OnFailure,
+                RestartConfig::Always => RestartPolicy::Always,
+            };
+            // The widget is built again from its config whenever it needs to
+            // be restarted.
+            let kind = widget.kind;
+            let position = position.clone();
+            let entry = cnx
+                .add_widget_fn(move || kind.clone().build(attr.clone(), &position))
+                .with_context(|| format!("Failed to create widget {idx}"))?
+                .align(alignment)
+                .restart_policy(restart_policy);
+            if let Some(signal) = widget.signal {
+                entry.refresh_signal(signal);
+            }
+        }
+
+        Ok(cnx)
+    }
+}
+
+// The attributes used for anything the configuration doesn't specify.
+fn base_attributes() -> Attributes {
+    Attributes {
+        font: Font::new("monospace 11"),
+        fg_color: Color::white(),
+        bg_color: None,
+        padding: Padding::new(8.0, 8.0, 0.0, 0.0),
+        valign: VerticalAlignment::Center,
+    }
+}
+
+impl AttributesConfig {
+    // Returns `attr` with any attributes set in this config overridden.
+    fn apply(&self, attr: &Attributes) -> Result<Attributes> {
+        let mut attr = attr.clone();
+        if let Some(font) = &self.font {
+            attr.font = Font::new(font);
+        }
+        if let Some(fg_color) = &self.fg_color {
+            attr.fg_color = fg_color.parse()?;
+        }
+        if let Some(bg_color) = &self.bg_color {
+            attr.bg_color = Some(bg_color.parse()?);
+        }
+        if let Some([left, right, top, bottom]) = self.padding {
+            attr.padding = Padding::new(left, right, top, bottom);
+        }
+        if let Some(valign) = self.valign {
+            attr.valign = match valign {
+                VerticalAlignmentConfig::Top => VerticalAlignment::Top,
+                VerticalAlignmentConfig::Center => VerticalAlignment::Center,
+                VerticalAlignmentConfig::Bottom => VerticalAlignment::Bottom,
+            };
+        }
+        Ok(attr)
+    }
+}
+
+// Replaces each `{name}` placeholder in `format` with its value.
+fn fill(format: &str, values: &[(&str, String)]) -> String {
+    values
+        .iter()
+        .fold(format.to_owned(), |text, (name, value)| {
+            text.replace(&format!("{{{name}}}"), value)
+        })
+}
+
+// Returns an update interval of `interval` seconds for a widget called
+// `name`, which can't be 0: the widget would never stop updating.
+fn checked_interval(name: &str, interval: u64) -> Result<Duration> {
+    if interval == 0 {
+        return Err(anyhow!("The interval of a {name} widget can't be 0"));
+    }
+    Ok(Duration::from_secs(interval))
+}
+
+// Returns a timeout of `timeout` seconds for a widget called `name`, which
+// can't be 0: every run would time out straight away.
+fn checked_timeout(name: &str, timeout: u64) -> Result<Duration> {
+    if timeout == 0 {
+        return Err(anyhow!("The timeout of a {name} widget can't be 0"));
+    }
+    Ok(Duration::from_secs(timeout))
+}
+
+// Sets the update interval of `widget` to `interval` seconds, if one is
+// configured.
+fn with_interval<W: Widget>(
+    widget: W,
+    interval: Option<u64>,
+    set: fn(W, Duration) -> W,
+) -> Result<W> {
+    match interval {
+        Some(interval) => {
+            let interval = checked_interval(widget.name(), interval)?;
+            Ok(set(widget, interval))
+        }
+        None => Ok(widget),
+    }
+}
+
+impl WidgetKind {
+    // Creates the widget, for a bar at `position`. This is called again each
+    // time the widget needs to be restarted, see `Cnx::add_widget_fn()`.
+    fn build(self, attr: Attributes, position: &Position) -> Result<Box<dyn Widget>> {
+        let widget: Box<dyn Widget> = match self {
+            WidgetKind::ActiveWindowTitle => Box::new(ActiveWindowTitle::new(attr)),
+            WidgetKind::Battery {
+                battery,
+                warning_color,
+                format,
+                icons,
+                interval,
+            } => {
+                let warning_color = match warning_color {
+                    Some(color) => color.parse()?,
+                    None => Color::red(),
+                };
+                let render = format.map(|format| -> Box<dyn Fn(BatteryInfo) -> String> {
+                    Box::new(move |info: BatteryInfo| {
+                        let icon = match info.status {
+                            Status::Full => &icons.full,
+                            Status::Charging => &icons.charging,
+                            Status::Discharging => &icons.discharging,
+                            Status::Unknown => &icons.unknown,
+                        };
+                        fill(
+                            &format,
+                            &[
+                                ("icon", icon.clone()),
+                                ("capacity", info.capacity.to_string()),
+                                ("status", format!("{:?}", info.status)),
+                            ],
+                        )
+                    })
+                });
+                let battery = Battery::new(attr, warning_color, battery, render);
+                Box::new(with_interval(battery, interval, Battery::with_interval)?)
+            }
+            WidgetKind::Clock { format, interval } => {
+                let clock = Clock::new(attr, format);
+                Box::new(with_interval(clock, interval, Clock::with_interval)?)
+            }
+            WidgetKind::Command {
+                command,
+                interval,
+                timeout,
+                kill_on_timeout,
+            } => {
+                let interval = checked_interval("command", interval)?;
+                let mut widget =
+                    Command::new(attr, command, interval).with_kill_on_timeout(kill_on_timeout);
+                if let Some(timeout) = timeout {
+                    widget = widget.with_timeout(checked_timeout("command", timeout)?);
+                }
+                Box::new(widget)
+            }
+            WidgetKind::Cpu { format, interval } => {
+                let render = format.map(|format| -> Box<dyn Fn(u64) -> String> {
+                    Box::new(move |usage: u64| fill(&format, &[("usage", usage.to_string())]))
+                });
+                let cpu = Cpu::new(attr, render)?;
+                Box::new(with_interval(cpu, interval, Cpu::with_interval)?)
+            }
+            WidgetKind::DiskUsage {
+                path,
+                format,
+                interval,
+            } => {
+                let render = format.map(|format| -> Box<dyn Fn(DiskInfo) -> String> {
+                    Box::new(move |info: DiskInfo| {
+                        let total = info.total.get_bytes();
+                        let used_percent = match total {
+                            0 => 0,
+                            total => info.used.get_bytes() * 100 / total,
+                        };
+                        let gib = |bytes: &Byte| bytes.get_adjusted_unit(ByteUnit::GiB).format(0);
+                        fill(
+                            &format,
+                            &[
+                                ("used_percent", used_percent.to_string()),
+                                ("used", gib(&info.used)),
+                                ("free", gib(&info.free)),
+                                ("total", gib(&info.total)),
+                            ],
+                        )
+                    })
+                });
+                let disk = DiskUsage::new(attr, path, render);
+                Box::new(with_interval(disk, interval, DiskUsage::with_interval)?)
+            }
+            WidgetKind::I3Bar { command, separator } => {
+                let widget = I3Bar::new(attr, command);
+                match separator {
+                    Some(separator) => Box::new(widget.with_separator(separator)),
+                    None => Box::new(widget),
+                }
+            }
+            WidgetKind::IpcText { name, text } => {
+                let widget = IpcText::new(attr, name);
+                match text {
+                    Some(text) => Box::new(widget.with_text(text)),
+                    None => Box::new(widget),
+                }
+            }
+            WidgetKind::LeftWM {
+                output,
+                focused,
+                visible,
+                busy,
+                empty,
+            } => {
+                let attrs = LeftWMAttributes {
+                    focused: focused.apply(&attr)?,
+                    visible: visible.apply(&attr)?,
+                    busy: busy.apply(&attr)?,
+                    empty: empty.apply(&attr)?,
+                };
+                Box::new(LeftWM::new(output, attrs))
+            }
+            WidgetKind::Sensors {
+                sensors,
+                interval,
+                timeout,
+                kill_on_timeout,
+            } => {
+                let mut sensors = Sensors::new(attr, sensors).with_kill_on_timeout(kill_on_timeout);
+                if let Some(timeout) = timeout {
+                    sensors = sensors.with_timeout(checked_timeout("sensors", timeout)?);
+                }
+                Box::new(with_interval(sensors, interval, Sensors::with_interval)?)
+            }
+            WidgetKind::Tray => Box::new(Tray::new(attr).with_position(position.clone())),
+            WidgetKind::Volume => Box::new(Volume::new(attr)),
+            WidgetKind::Wireless {
+                interface,
+                threshold,
+                interval,
+            } => {
+                let threshold = if threshold {
+                    Some(Threshold::default())
+                } else {
+                    None
+                };
+                let wireless = Wireless::new(attr, interface, threshold);
+                Box::new(with_interval(wireless, interval, Wireless::with_interval)?)
+            }
+        };
+        Ok(widget)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{fill, Config, MonitorsConfig, RestartConfig, WidgetKind, DEFAULT_CONFIG};
+
+    #[test]
+    fn default_config() {
+        let config = Config::parse(DEFAULT_CONFIG).unwrap();
+        assert_eq!(config.widgets.len(), 10);
+        config.into_cnx().unwrap();
+    }
+
+    #[test]
+    fn monitors() {
+        let config = Config::parse(r#"monitors = "all""#).unwrap();
+        assert!(matches!(config.monitors, Some(MonitorsConfig::All(_))));
+
+        let config = Config::parse(r#"monitors = ["eDP-1"]"#).unwrap();
+        assert!(
+            matches!(config.monitors, Some(MonitorsConfig::Named(ref names)) if names == &["eDP-1"])
+        );
+    }
+
+    #[test]
+    fn widgets() {
+        let config = Config::parse(
+            r##"
+            [[widgets]]
+            type = "clock"
+            align = "right"
+            attributes = { fg_color = "#ff0000" }
+
+            [[widgets]]
+            type = "volume"
+            restart = "on-failure"
+            signal = 3
+            "##,
+        )
+        .unwrap();
+        assert!(matches!(
+            config.widgets[0].kind,
+            WidgetKind::Clock {
+                format: None,
+                interval: None
+            }
+        ));
+        assert!(matches!(config.widgets[1].kind, WidgetKind::Volume));
+        assert!(matches!(config.widgets[0].restart, RestartConfig::Always));
+        assert!(matches!(
+            config.widgets[1].restart,
+            RestartConfig::OnFailure
+        ));
+        assert_eq!(config.widgets[0].signal, None);
+        assert_eq!(config.widgets[1].signal, Some(3));
+
+        let invalid = Config::parse(
+            r##"
+            [[widgets]]
+            type = "clock"
+            attributes = { fg_color = "red" }
+            "##,
+        )
+        .unwrap();
+        assert!(invalid.into_cnx().is_err());
+
+        let zero_interval = Config::parse(
+            r##"
+            [[widgets]]
+            type = "clock"
+            interval = 0
+            "##,
+        )
+        .unwrap();
+        let err = zero_interval.into_cnx().err().unwrap();
+        assert!(format!("{err:#}").contains("clock widget"));
+
+        let zero_timeout = Config::parse(
+            r##"
+            [[widgets]]
+            type = "command"
+            command = "date"
+            interval = 10
+            timeout = 0
+            "##,
+        )
+        .unwrap();
+        let err = zero_timeout.into_cnx().err().unwrap();
+        assert!(format!("{err:#}").contains("timeout of a command widget"));
+    }
+
+    #[test]
+    fn errors() {
+        let config = Config::parse("").unwrap();
+        assert!(config.errors.show);
+        assert_eq!(config.errors.format, "!");
+
+        let config = Config::parse(
+            r#"
+            [errors]
+            show = false
+            "#,
+        )
+        .unwrap();
+        assert!(!config.errors.show);
+    }
+
+    #[test]
+    fn fill_placeholders() {
+        let text = fill("{icon} {capacity}%", &[("capacity", "42".to_owned())]);
+        assert_eq!(text, "{icon} 42%");
+    }
+}
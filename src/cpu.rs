@@ -1,12 +1,11 @@
 use anyhow::{anyhow, Result};
+use crate::schedule;
 use crate::text::{Attributes, Text};
 use crate::widget::{Widget, WidgetStream};
 use std::fs::File;
 use std::io::BufRead;
 use std::io::BufReader;
 use std::time::Duration;
-use tokio::time;
-use tokio_stream::wrappers::IntervalStream;
 use tokio_stream::StreamExt;
 
 /// Represents CPU widget used to show current CPU consumptiong
@@ -14,6 +13,7 @@ pub struct Cpu {
     attr: Attributes,
     cpu_data: CpuData,
     render: Option<Box<dyn Fn(u64) -> String>>,
+    update_interval: Duration,
 }
 
 impl Cpu {
@@ -45,6 +45,7 @@ impl Cpu {
     ///     fg_color: Color::white(),
     ///     bg_color: None,
     ///     padding: Padding::new(8.0, 8.0, 0.0, 0.0),
+    ///     valign: VerticalAlignment::Center,
     /// };
     ///
     /// let mut cnx = Cnx::new(Position::Top);
@@ -59,9 +60,19 @@ impl Cpu {
             attr,
             cpu_data,
             render,
+            update_interval: Duration::from_secs(10),
         })
     }
 
+    /// Sets how often the usage is updated, which is also the period it's
+    /// averaged over. The default is ten seconds.
+    pub fn with_interval(self, update_interval: Duration) -> Self {
+        Self {
+            update_interval,
+            ..self
+        }
+    }
+
     fn tick(&mut self) -> Result<Vec<Text>> {
         let cpu_data = CpuData::get_values()?;
 
@@ -144,9 +155,8 @@ impl CpuData {
 
 impl Widget for Cpu {
     fn into_stream(mut self: Box<Self>) -> Result<WidgetStream> {
-        let ten_seconds = Duration::from_secs(10);
-        let interval = time::interval(ten_seconds);
-        let stream = IntervalStream::new(interval).map(move |_| self.tick());
+        let ticks = schedule::ticks(self.update_interval);
+        let stream = ticks.map(move |_| self.tick());
         Ok(Box::pin(stream))
     }
 }
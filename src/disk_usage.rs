@@ -1,11 +1,10 @@
 use anyhow::Result;
 use byte_unit::{Byte, ByteUnit};
+use crate::schedule;
 use crate::text::{Attributes, Text};
 use crate::widget::{Widget, WidgetStream};
 use nix::sys::statvfs::statvfs;
 use std::time::Duration;
-use tokio::time;
-use tokio_stream::wrappers::IntervalStream;
 use tokio_stream::StreamExt;
 
 /// Represent Information about the mounted filesystem
@@ -40,6 +39,7 @@ pub struct DiskUsage {
     attr: Attributes,
     path: String,
     render: Option<Box<dyn Fn(DiskInfo) -> String>>,
+    update_interval: Duration,
 }
 
 impl DiskUsage {
@@ -73,6 +73,7 @@ impl DiskUsage {
     ///     fg_color: Color::white(),
     ///     bg_color: None,
     ///     padding: Padding::new(8.0, 8.0, 0.0, 0.0),
+    ///     valign: VerticalAlignment::Center,
     /// };
     ///
     /// let mut cnx = Cnx::new(Position::Top);
@@ -86,7 +87,20 @@ impl DiskUsage {
         path: String,
         render: Option<Box<dyn Fn(DiskInfo) -> String>>,
     ) -> Self {
-        Self { attr, render, path }
+        Self {
+            attr,
+            render,
+            path,
+            update_interval: Duration::from_secs(3600),
+        }
+    }
+
+    /// Sets how often the disk usage is updated. The default is an hour.
+    pub fn with_interval(self, update_interval: Duration) -> Self {
+        Self {
+            update_interval,
+            ..self
+        }
     }
 
     fn tick(&self) -> Result<Vec<Text>> {
@@ -113,9 +127,8 @@ impl DiskUsage {
 
 impl Widget for DiskUsage {
     fn into_stream(self: Box<Self>) -> Result<WidgetStream> {
-        let one_hour = Duration::from_secs(3600);
-        let interval = time::interval(one_hour);
-        let stream = IntervalStream::new(interval).map(move |_| self.tick());
+        let ticks = schedule::ticks(self.update_interval);
+        let stream = ticks.map(move |_| self.tick());
 
         Ok(Box::pin(stream))
     }
@@ -0,0 +1,470 @@
+//! A widget that shows the output of a program speaking the i3bar protocol.
+//!
+//! This lets status generators written for i3bar, like i3status, i3blocks
+//! and bumblebee-status, be used as they are. See
+//! <https://i3wm.org/docs/i3bar-protocol.html> for the protocol.
+
+use anyhow::{anyhow, Context, Result};
+use async_stream::try_stream;
+use cairo::{Format, ImageSurface, Surface};
+use futures::channel::mpsc;
+use futures::StreamExt;
+use log::debug;
+use serde_derive::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::process::Stdio;
+use std::rc::Rc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{ChildStdin, Command};
+use tokio::task;
+
+use crate::mouse::{MouseEvent, MouseEventKind, MouseHandler};
+use crate::text::{Attributes, Text};
+use crate::widget::{Widget, WidgetStream};
+
+// The gap i3bar leaves after a block, if the block doesn't say.
+const DEFAULT_SEPARATOR_BLOCK_WIDTH: f64 = 9.0;
+
+// The first line a program speaking the protocol writes.
+#[derive(Debug, Deserialize)]
+struct Header {
+    version: u32,
+    #[serde(default)]
+    click_events: bool,
+}
+
+// One block of a status line. Fields that have no equivalent on this bar
+// (like `short_text` and `border`) are ignored.
+#[derive(Debug, Deserialize)]
+struct Block {
+    full_text: String,
+    color: Option<String>,
+    background: Option<String>,
+    #[serde(default = "default_separator")]
+    separator: bool,
+    separator_block_width: Option<f64>,
+    min_width: Option<MinWidth>,
+    #[serde(default)]
+    align: Align,
+    markup: Option<String>,
+    name: Option<String>,
+    instance: Option<String>,
+}
+
+fn default_separator() -> bool {
+    true
+}
+
+// Either a width in pixels, or a text that the block is at least as wide as.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum MinWidth {
+    Pixels(f64),
+    Text(String),
+}
+
+// Where the text of a block narrower than its `min_width` goes.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+// Identifies the block a click was on, for the program to tell them apart.
+#[derive(Clone, Debug, Default, Serialize)]
+struct BlockId {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instance: Option<String>,
+}
+
+// A click event, as sent to the program.
+#[derive(Debug, Serialize)]
+struct Click {
+    #[serde(flatten)]
+    block: BlockId,
+    button: u8,
+    modifiers: Vec<&'static str>,
+    // The position of the pointer on the screen.
+    x: i32,
+    y: i32,
+    // The position of the pointer relative to the block, and the size of
+    // the block.
+    relative_x: i32,
+    relative_y: i32,
+    width: i32,
+    height: i32,
+}
+
+/// Shows the status line of a program speaking the i3bar protocol, such as
+/// i3status, i3blocks or bumblebee-status.
+///
+/// Each block is shown as a text with its `full_text`, `color`,
+/// `background`, `min_width`, `align` and `markup`. Blocks are followed by a
+/// separator, unless they set `separator` to `false`, in which case only
+/// their `separator_block_width` is left empty.
+///
+/// If the program asks for click events in its header, clicks on its blocks
+/// are written to its stdin.
+pub struct I3Bar {
+    attr: Attributes,
+    command: String,
+    separator: String,
+    // The block each of the texts most recently shown belongs to, if any
+    // (separators belong to none).
+    blocks: Rc<RefCell<Vec<Option<BlockId>>>>,
+    clicks: Option<mpsc::UnboundedReceiver<Click>>,
+}
+
+impl I3Bar {
+    /// Creates a new [`I3Bar`] widget, which runs `command` with `sh -c` and
+    /// shows its output.
+    ///
+    /// For example, `I3Bar::new(attr, "i3status".into())`.
+    pub fn new(attr: Attributes, command: String) -> Self {
+        Self {
+            attr,
+            command,
+            separator: "|".to_owned(),
+            blocks: Rc::new(RefCell::new(Vec::new())),
+            clicks: None,
+        }
+    }
+
+    /// Sets the text shown between blocks, which is `|` by default.
+    pub fn with_separator(self, separator: String) -> Self {
+        Self { separator, ..self }
+    }
+
+    // Returns the attributes of the texts of `block`, with its colors.
+    fn block_attributes(&self, block: &Block) -> Result<Attributes> {
+        let mut attr = self.attr.clone();
+        if let Some(color) = &block.color {
+            attr.fg_color = color.parse()?;
+        }
+        if let Some(background) = &block.background {
+            attr.bg_color = Some(background.parse()?);
+        }
+        Ok(attr)
+    }
+
+    // Returns the texts to show for a status line, along with the block that
+    // each text belongs to.
+    //
+    // Blocks without any text are left out, separator and all, as i3bar
+    // does.
+    fn texts(
+        &self,
+        blocks: Vec<Block>,
+        surface: &Surface,
+    ) -> Result<(Vec<Text>, Vec<Option<BlockId>>)> {
+        let blocks: Vec<Block> = blocks
+            .into_iter()
+            .filter(|block| !block.full_text.is_empty())
+            .collect();
+        let mut texts = Vec::with_capacity(blocks.len() * 2);
+        let mut ids = Vec::with_capacity(blocks.len() * 2);
+        let count = blocks.len();
+        for (idx, block) in blocks.into_iter().enumerate() {
+            // A block with a color we can't parse is still shown, just
+            // without its colors.
+            let attr = self.block_attributes(&block).unwrap_or_else(|err| {
+                debug!("Ignoring the colors of {:?}: {err:#}", block.full_text);
+                self.attr.clone()
+            });
+            let text = Text {
+                attr,
+                text: block.full_text,
+                stretch: false,
+                markup: block.markup.as_deref() == Some("pango"),
+            };
+            let text = match &block.min_width {
+                Some(min_width) => pad(text, min_width, &block.align, surface)?,
+                None => text,
+            };
+            texts.push(text);
+            ids.push(Some(BlockId {
+                name: block.name,
+                instance: block.instance,
+            }));
+
+            if idx + 1 == count {
+                break;
+            }
+            let width = block
+                .separator_block_width
+                .unwrap_or(DEFAULT_SEPARATOR_BLOCK_WIDTH);
+            let mut attr = self.attr.clone();
+            attr.bg_color = None;
+            let text = if block.separator {
+                attr.padding.left = (width / 2.0).floor();
+                attr.padding.right = (width / 2.0).ceil();
+                self.separator.clone()
+            } else {
+                attr.padding.left = width;
+                attr.padding.right = 0.0;
+                String::new()
+            };
+            texts.push(Text {
+                attr,
+                text,
+                stretch: false,
+                markup: false,
+            });
+            ids.push(None);
+        }
+        Ok((texts, ids))
+    }
+}
+
+// Widens `text` to `min_width`, placing it as `align` says.
+fn pad(mut text: Text, min_width: &MinWidth, align: &Align, surface: &Surface) -> Result<Text> {
+    let padding = text.attr.padding.left + text.attr.padding.right;
+    let min_width = match min_width {
+        MinWidth::Pixels(width) => *width,
+        MinWidth::Text(min_text) => {
+            let min_text = Text {
+                text: min_text.clone(),
+                ..text.clone()
+            };
+            min_text.compute(surface)?.width - padding
+        }
+    };
+    let width = text.clone().compute(surface)?.width - padding;
+    let extra = min_width - width;
+    if extra > 0.0 {
+        let padding = &mut text.attr.padding;
+        match align {
+            Align::Left => padding.right += extra,
+            Align::Center => {
+                padding.left += (extra / 2.0).floor();
+                padding.right += (extra / 2.0).ceil();
+            }
+            Align::Right => padding.left += extra,
+        }
+    }
+    Ok(text)
+}
+
+// Parses the header, which is the first line the program writes.
+fn read_header(line: Option<String>) -> Result<Header> {
+    let line = line.unwrap_or_default();
+    let header: Header = serde_json::from_str(&line)
+        .with_context(|| format!("Expected an i3bar protocol header, got {line:?}"))?;
+    if header.version != 1 {
+        return Err(anyhow!(
+            "Unsupported i3bar protocol version {}",
+            header.version
+        ));
+    }
+    Ok(header)
+}
+
+// Returns the blocks of a line of the infinite array that follows the
+// header, or `None` if the line has none (like the `[` that opens the
+// array).
+fn parse_line(line: &str) -> Result<Option<Vec<Block>>> {
+    // Each status line is an array, separated from the previous one by a
+    // comma, and the first may share its line with the `[` opening the
+    // infinite array.
+    let mut line = line.trim();
+    if line.starts_with("[[") || line == "[" {
+        line = &line[1..];
+    }
+    let line = line.trim_start_matches(',').trim_end_matches(',').trim();
+    if line.is_empty() {
+        return Ok(None);
+    }
+    let blocks = serde_json::from_str(line).context("Invalid status line")?;
+    Ok(Some(blocks))
+}
+
+// Writes clicks to the program's stdin, as the elements of an infinite array,
+// until the mouse handler is dropped or the program goes away.
+async fn forward_clicks(mut clicks: mpsc::UnboundedReceiver<Click>, mut stdin: ChildStdin) {
+    let mut separator = "[\n";
+    while let Some(click) = clicks.next().await {
+        let click = match serde_json::to_string(&click) {
+            Ok(click) => click,
+            Err(err) => {
+                debug!("Failed to encode click: {err}");
+                continue;
+            }
+        };
+        let line = format!("{separator}{click}\n");
+        if let Err(err) = stdin.write_all(line.as_bytes()).await {
+            debug!("Failed to send click: {err}");
+            return;
+        }
+        separator = ",";
+    }
+}
+
+impl Widget for I3Bar {
+    fn mouse_handler(&mut self) -> Option<MouseHandler> {
+        let (sender, clicks) = mpsc::unbounded();
+        self.clicks = Some(clicks);
+        let blocks = self.blocks.clone();
+        Some(Box::new(move |event: MouseEvent| {
+            if event.kind != MouseEventKind::Press {
+                return Ok(());
+            }
+            let block = match blocks.borrow().get(event.index) {
+                Some(Some(block)) => block.clone(),
+                _ => return Ok(()),
+            };
+            let modifiers = [
+                (event.modifiers.shift, "Shift"),
+                (event.modifiers.control, "Control"),
+                (event.modifiers.alt, "Mod1"),
+                (event.modifiers.super_key, "Mod4"),
+            ];
+            let click = Click {
+                block,
+                button: event.button.code(),
+                modifiers: modifiers
+                    .iter()
+                    .filter(|(held, _)| *held)
+                    .map(|&(_, name)| name)
+                    .collect(),
+                x: event.root_x as i32,
+                y: event.root_y as i32,
+                relative_x: event.x as i32,
+                relative_y: event.y as i32,
+                width: event.width as i32,
+                height: event.height as i32,
+            };
+            // The program may not have asked for clicks, or may have exited.
+            let _ = sender.unbounded_send(click);
+            Ok(())
+        }))
+    }
+
+    fn into_stream(mut self: Box<Self>) -> Result<WidgetStream> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to run `{}`", self.command))?;
+        let stdin = child.stdin.take();
+        let stdout = child.stdout.take().context("Failed to read stdout")?;
+        let clicks = self.clicks.take();
+        // For measuring texts that have a `min_width`.
+        let surface = ImageSurface::create(Format::ARgb32, 1, 1)
+            .map_err(|status| anyhow!("ImageSurface::create: {}", status))?;
+
+        let stream = try_stream! {
+            let mut lines = BufReader::new(stdout).lines();
+            let header = read_header(lines.next_line().await?)?;
+            if let (true, Some(clicks), Some(stdin)) = (header.click_events, clicks, stdin) {
+                task::spawn_local(forward_clicks(clicks, stdin));
+            }
+
+            while let Some(line) = lines.next_line().await? {
+                if let Some(blocks) = parse_line(&line)? {
+                    let (texts, ids) = self.texts(blocks, &surface)?;
+                    *self.blocks.borrow_mut() = ids;
+                    yield texts;
+                }
+            }
+            // The program only stops writing status lines when it exits.
+            let status = child.wait().await?;
+            let exited: Result<()> = Err(anyhow!("Exited with {status}"));
+            exited?;
+        };
+        Ok(Box::pin(stream))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_line, read_header, BlockId, Click, I3Bar};
+    use crate::text::{Attributes, Color, Font, Padding, VerticalAlignment};
+    use cairo::{Format, ImageSurface};
+
+    #[test]
+    fn status_lines() {
+        assert!(parse_line("[").unwrap().is_none());
+        let line = r##"[{"full_text":"E: down","color":"#ff0000","separator":false}],"##;
+        let blocks = parse_line(line).unwrap().unwrap();
+        assert_eq!(blocks[0].full_text, "E: down");
+        assert_eq!(blocks[0].color.as_deref(), Some("#ff0000"));
+        assert!(!blocks[0].separator);
+
+        let line = r#",[{"full_text":"a","name":"disk"},{"full_text":"b","min_width":"100%"}]"#;
+        let blocks = parse_line(line).unwrap().unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].name.as_deref(), Some("disk"));
+        assert!(blocks[1].separator);
+        assert!(parse_line(r#"[[{"full_text":"a"}]"#).unwrap().is_some());
+        assert!(parse_line("not json").is_err());
+    }
+
+    #[test]
+    fn texts() {
+        let attr = Attributes {
+            font: Font::new("monospace 11"),
+            fg_color: Color::white(),
+            bg_color: None,
+            padding: Padding::new(0.0, 0.0, 0.0, 0.0),
+            valign: VerticalAlignment::Center,
+        };
+        let widget = I3Bar::new(attr, "true".to_owned());
+        let surface = ImageSurface::create(Format::ARgb32, 1, 1).unwrap();
+        let line = r##"[{"full_text":"a","color":"not a color"},{"full_text":""},{"full_text":"b","color":"#ff0000"}]"##;
+        let blocks = parse_line(line).unwrap().unwrap();
+        let (texts, ids) = widget.texts(blocks, &surface).unwrap();
+
+        // The empty block is left out along with its separator.
+        assert_eq!(texts.len(), 3);
+        assert_eq!(ids.len(), 3);
+        assert_eq!(texts[0].text, "a");
+        assert_eq!(texts[1].text, "|");
+        assert_eq!(texts[2].text, "b");
+        // Colors that can't be parsed are ignored.
+        assert_eq!(texts[0].attr.fg_color, Color::white());
+        assert_eq!(texts[2].attr.fg_color, "#ff0000".parse::<Color>().unwrap());
+    }
+
+    #[test]
+    fn header() {
+        let header = read_header(Some(r#"{"version":1,"click_events":true}"#.into())).unwrap();
+        assert!(header.click_events);
+        assert!(
+            !read_header(Some(r#"{"version":1}"#.into()))
+                .unwrap()
+                .click_events
+        );
+        assert!(read_header(Some("plain text".into())).is_err());
+        assert!(read_header(None).is_err());
+    }
+
+    #[test]
+    fn click_events() {
+        let click = Click {
+            block: BlockId {
+                name: Some("disk".to_owned()),
+                instance: None,
+            },
+            button: 1,
+            modifiers: vec!["Shift"],
+            x: 103,
+            y: 4,
+            relative_x: 3,
+            relative_y: 4,
+            width: 40,
+            height: 20,
+        };
+        assert_eq!(
+            serde_json::to_string(&click).unwrap(),
+            r#"{"name":"disk","button":1,"modifiers":["Shift"],"x":103,"y":4,"relative_x":3,"relative_y":4,"width":40,"height":20}"#
+        );
+    }
+}
@@ -0,0 +1,359 @@
+//! Controlling a running bar over a Unix socket.
+//!
+//! Each running bar listens on a socket named after the bar (see
+//! [`socket_path()`]). Clients send one [`Request`] per line, encoded as JSON,
+//! and get one [`Response`] per line back, e.g.:
+//!
+//! ```text
+//! > {"command":"refresh","widget":"volume"}
+//! < "ok"
+//! > {"command":"list"}
+//! < {"widgets":[{"index":0,"name":"clock","texts":["12:00"],"error":null}]}
+//! ```
+//!
+//! `rusty-bar msg` sends requests from the command line, for scripts and
+//! window manager key bindings.
+//!
+//! [`socket_path()`]: fn.socket_path.html
+//! [`Request`]: enum.Request.html
+//! [`Response`]: enum.Response.html
+
+use anyhow::{anyhow, Context, Result};
+use futures::channel::{mpsc, oneshot};
+use futures::{SinkExt, Stream};
+use log::{debug, warn};
+use serde_derive::{Deserialize, Serialize};
+use std::env;
+use std::fs::{self, DirBuilder};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::os::unix::net::UnixStream as StdUnixStream;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context as TaskContext, Poll};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader as AsyncBufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::task::{self, JoinHandle};
+
+/// A request to a running bar.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request {
+    /// Lists the widgets, along with what they show and whether they have
+    /// failed.
+    List,
+    /// Restarts the given widgets straight away, so that they show
+    /// up-to-date content.
+    Refresh { widget: WidgetId },
+    /// Hides the bar.
+    Hide,
+    /// Shows the bar after it has been hidden.
+    Show,
+    /// Hides the bar if it's shown, and shows it if it's hidden.
+    Toggle,
+    /// Reloads the configuration file.
+    Reload,
+    /// Sets the text of the [`IpcText`] widgets called `widget`, clearing it
+    /// after `ttl` seconds if given.
+    ///
+    /// [`IpcText`]: ../ipc_text/struct.IpcText.html
+    Set {
+        widget: String,
+        text: String,
+        ttl: Option<u64>,
+    },
+}
+
+/// Selects widgets by their index (counting from zero, in the order they were
+/// added) or by their name, such as `volume`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum WidgetId {
+    Index(usize),
+    Name(String),
+}
+
+/// The response of a running bar to a [`Request`].
+///
+/// [`Request`]: enum.Request.html
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Response {
+    /// The request was carried out.
+    Ok,
+    /// The response to [`Request::List`].
+    ///
+    /// [`Request::List`]: enum.Request.html#variant.List
+    Widgets(Vec<WidgetInfo>),
+    /// The request failed, with the given message.
+    Error(String),
+}
+
+/// What a running bar knows about one of its widgets.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WidgetInfo {
+    pub index: usize,
+    pub name: String,
+    /// The texts the widget currently shows.
+    pub texts: Vec<String>,
+    /// The error the widget failed with, if it has failed and not yet
+    /// recovered.
+    pub error: Option<String>,
+}
+
+/// Returns the path of the socket that the bar called `name` listens on.
+///
+/// This is `$XDG_RUNTIME_DIR/rusty-bar/<name>.sock`, or
+/// `/tmp/rusty-bar-<uid>/<name>.sock` if `$XDG_RUNTIME_DIR` isn't set.
+pub fn socket_path(name: &str) -> PathBuf {
+    let dir = match env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(runtime_dir) => Path::new(&runtime_dir).join("rusty-bar"),
+        None => env::temp_dir().join(format!("rusty-bar-{}", nix::unistd::getuid())),
+    };
+    dir.join(format!("{name}.sock"))
+}
+
+/// Sends `request` to the running bar called `name` and waits for its
+/// response.
+pub fn send(name: &str, request: &Request) -> Result<Response> {
+    let path = socket_path(name);
+    let mut stream = StdUnixStream::connect(&path).with_context(|| {
+        format!(
+            "Failed to connect to {}, is the bar running?",
+            path.display()
+        )
+    })?;
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    if line.is_empty() {
+        return Err(anyhow!("The bar closed the connection without responding"));
+    }
+    let response = serde_json::from_str(&line).context("Invalid response")?;
+    Ok(response)
+}
+
+// A request received from a client, along with where to send the response.
+pub(crate) type Incoming = (Request, oneshot::Sender<Response>);
+
+// Listens on the socket of the bar called `name`, yielding each request
+// received. The socket is removed when this is dropped.
+pub(crate) struct Server {
+    path: PathBuf,
+    requests: mpsc::Receiver<Incoming>,
+    task: JoinHandle<()>,
+}
+
+impl Server {
+    // Starts listening. This must be called from within a `LocalSet`.
+    pub fn new(name: &str) -> Result<Server> {
+        let path = socket_path(name);
+        if let Some(dir) = path.parent() {
+            DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+            check_private(dir)?;
+        }
+        // A socket that nothing is listening on was left behind by a bar
+        // that didn't exit cleanly.
+        if path.exists() {
+            if StdUnixStream::connect(&path).is_ok() {
+                return Err(anyhow!("Another bar called {name} is already running"));
+            }
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+        }
+        let listener = UnixListener::bind(&path)
+            .with_context(|| format!("Failed to listen on {}", path.display()))?;
+
+        let (sender, requests) = mpsc::channel(0);
+        let task = task::spawn_local(accept(listener, sender));
+        Ok(Server {
+            path,
+            requests,
+            task,
+        })
+    }
+}
+
+// Checks that `dir` is a directory (not a symlink to one) that only we can
+// use. Otherwise, e.g. if another user created it first in a shared `/tmp`,
+// they could intercept requests or replace our socket.
+fn check_private(dir: &Path) -> Result<()> {
+    let metadata = fs::symlink_metadata(dir)
+        .with_context(|| format!("Failed to get metadata of {}", dir.display()))?;
+    if !metadata.file_type().is_dir() {
+        return Err(anyhow!("{} isn't a directory", dir.display()));
+    }
+    if metadata.uid() != nix::unistd::getuid().as_raw() {
+        return Err(anyhow!("{} is owned by another user", dir.display()));
+    }
+    let mode = metadata.mode() & 0o777;
+    if mode != 0o700 {
+        return Err(anyhow!(
+            "{} has mode {mode:o}, rather than 700",
+            dir.display()
+        ));
+    }
+    Ok(())
+}
+
+impl Stream for Server {
+    type Item = Incoming;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Incoming>> {
+        Pin::new(&mut self.requests).poll_next(cx)
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.task.abort();
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+async fn accept(listener: UnixListener, sender: mpsc::Sender<Incoming>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let sender = sender.clone();
+                task::spawn_local(async move {
+                    if let Err(err) = serve(stream, sender).await {
+                        debug!("Error serving IPC client: {err:#}");
+                    }
+                });
+            }
+            Err(err) => {
+                warn!("Error accepting IPC connection: {err}");
+                return;
+            }
+        }
+    }
+}
+
+// Handles the requests of one client until it disconnects.
+async fn serve(stream: UnixStream, mut sender: mpsc::Sender<Incoming>) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = AsyncBufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        let response = match serde_json::from_str(&line) {
+            Ok(request) => {
+                let (respond, response) = oneshot::channel();
+                sender.send((request, respond)).await?;
+                response.await?
+            }
+            Err(err) => Response::Error(format!("Invalid request: {err}")),
+        };
+        let mut line = serde_json::to_string(&response)?;
+        line.push('\n');
+        writer.write_all(line.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+impl Request {
+    /// Parses a request from the arguments of `rusty-bar msg`, e.g.
+    /// `["refresh", "volume"]`.
+    pub fn from_args(args: &[String]) -> Result<Request> {
+        let request = match args {
+            [command] if command == "list" => Request::List,
+            [command, widget] if command == "refresh" => {
+                let widget = match widget.parse() {
+                    Ok(index) => WidgetId::Index(index),
+                    Err(_) => WidgetId::Name(widget.clone()),
+                };
+                Request::Refresh { widget }
+            }
+            [command] if command == "hide" => Request::Hide,
+            [command] if command == "show" => Request::Show,
+            [command] if command == "toggle" => Request::Toggle,
+            [command] if command == "reload" => Request::Reload,
+            [command, widget, text, rest @ ..] if command == "set" => {
+                let ttl = match rest {
+                    [] => None,
+                    [flag, ttl] if flag == "--ttl" => {
+                        let ttl = ttl.parse().with_context(|| format!("Invalid TTL: {ttl}"))?;
+                        Some(ttl)
+                    }
+                    _ => return Err(anyhow!("Unknown message: {}", args.join(" "))),
+                };
+                Request::Set {
+                    widget: widget.clone(),
+                    text: text.clone(),
+                    ttl,
+                }
+            }
+            _ => return Err(anyhow!("Unknown message: {}", args.join(" "))),
+        };
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_private, Request, Response, WidgetId};
+    use std::fs;
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    #[test]
+    fn protocol() {
+        let request: Request =
+            serde_json::from_str(r#"{"command":"refresh","widget":"volume"}"#).unwrap();
+        let widget = WidgetId::Name("volume".to_owned());
+        assert_eq!(request, Request::Refresh { widget });
+        let request: Request = serde_json::from_str(r#"{"command":"refresh","widget":2}"#).unwrap();
+        let widget = WidgetId::Index(2);
+        assert_eq!(request, Request::Refresh { widget });
+
+        assert_eq!(serde_json::to_string(&Response::Ok).unwrap(), r#""ok""#);
+        let error = Response::Error("No such widget".to_owned());
+        assert_eq!(
+            serde_json::to_string(&error).unwrap(),
+            r#"{"error":"No such widget"}"#
+        );
+    }
+
+    #[test]
+    fn args() {
+        let args = |args: &[&str]| -> Vec<String> { args.iter().map(|&arg| arg.into()).collect() };
+        assert_eq!(Request::from_args(&args(&["list"])).unwrap(), Request::List);
+        assert_eq!(
+            Request::from_args(&args(&["refresh", "3"])).unwrap(),
+            Request::Refresh {
+                widget: WidgetId::Index(3)
+            }
+        );
+        assert!(Request::from_args(&args(&["refresh"])).is_err());
+        assert_eq!(
+            Request::from_args(&args(&["set", "mail", "3 new", "--ttl", "60"])).unwrap(),
+            Request::Set {
+                widget: "mail".to_owned(),
+                text: "3 new".to_owned(),
+                ttl: Some(60)
+            }
+        );
+    }
+
+    #[test]
+    fn private_dir() {
+        let dir = std::env::temp_dir().join(format!("rusty-bar-test-{}", std::process::id()));
+        fs::create_dir(&dir).unwrap();
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700)).unwrap();
+        assert!(check_private(&dir).is_ok());
+
+        let link = dir.with_extension("link");
+        symlink(&dir, &link).unwrap();
+        assert!(check_private(&link).is_err());
+        fs::remove_file(&link).unwrap();
+
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(check_private(&dir).is_err());
+        fs::remove_dir(&dir).unwrap();
+    }
+}
@@ -0,0 +1,239 @@
+use anyhow::{anyhow, Result};
+use async_stream::stream;
+use futures::channel::mpsc;
+use futures::future;
+use futures::StreamExt;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::{self, Instant};
+
+use crate::text::{Attributes, Text};
+use crate::widget::{Widget, WidgetStream};
+
+// The text sent to an `IpcText` widget, and when it expires, if it was sent
+// with a TTL.
+#[derive(Clone, Debug)]
+struct Message {
+    text: String,
+    expiry: Option<Instant>,
+}
+
+impl Message {
+    fn expired(&self) -> bool {
+        self.expiry.map_or(false, |expiry| expiry <= Instant::now())
+    }
+}
+
+// The `IpcText` widgets with a given name. There may be more than one, in
+// which case they all show the same text.
+#[derive(Default)]
+struct Widgets {
+    // One for each running widget.
+    senders: Vec<mpsc::UnboundedSender<Message>>,
+    // The most recent message, which a widget that is (re)started later
+    // starts out showing until it expires.
+    last: Option<Message>,
+}
+
+lazy_static! {
+    // The `IpcText` widgets that have been started, by name.
+    static ref WIDGETS: Mutex<HashMap<String, Widgets>> = Mutex::new(HashMap::new());
+}
+
+/// Shows text that is set from outside the bar, with `rusty-bar msg set`.
+///
+/// Rather than polling for its content like [`Command`], this widget waits
+/// for scripts to push it, so the bar updates as soon as they do, e.g.:
+///
+/// ```text
+/// rusty-bar msg set mail '<span foreground="red">3 new</span>'
+/// rusty-bar msg set deploy 'Deployed!' --ttl 60
+/// ```
+///
+/// Text sent with a TTL (in seconds) is cleared once it has expired, and
+/// empty text hides the widget.
+///
+/// [`Command`]: ../command/struct.Command.html
+pub struct IpcText {
+    attr: Attributes,
+    name: String,
+    text: String,
+}
+
+impl IpcText {
+    /// Creates a new [`IpcText`] widget, which shows the text sent to `name`.
+    ///
+    /// The widget is empty until some text is sent to it, unless initial text
+    /// is set with [`IpcText::with_text()`].
+    pub fn new(attr: Attributes, name: String) -> Self {
+        Self {
+            attr,
+            name,
+            text: String::new(),
+        }
+    }
+
+    /// Sets the text shown until some is sent to the widget.
+    ///
+    /// Once some has been, a widget with the same name that is started later
+    /// (e.g. because this one was restarted) shows that instead, unless its
+    /// TTL has passed.
+    pub fn with_text(self, text: String) -> Self {
+        Self { text, ..self }
+    }
+
+    fn texts(&self, text: String) -> Vec<Text> {
+        if text.is_empty() {
+            return vec![];
+        }
+        vec![Text {
+            attr: self.attr.clone(),
+            text,
+            stretch: false,
+            markup: true,
+        }]
+    }
+}
+
+impl Widget for IpcText {
+    fn into_stream(self: Box<Self>) -> Result<WidgetStream> {
+        let (sender, mut messages) = mpsc::unbounded();
+        let first = {
+            let mut widgets = WIDGETS.lock().unwrap();
+            let widgets = widgets.entry(self.name.clone()).or_default();
+            widgets.senders.push(sender);
+            match &widgets.last {
+                Some(message) if !message.expired() => message.clone(),
+                _ => Message {
+                    text: self.text.clone(),
+                    expiry: None,
+                },
+            }
+        };
+
+        let stream = stream! {
+            yield Ok(self.texts(first.text));
+            let mut expiry = first.expiry;
+            loop {
+                let expired = async move {
+                    match expiry {
+                        Some(expiry) => time::sleep_until(expiry).await,
+                        None => future::pending().await,
+                    }
+                };
+                let text = tokio::select! {
+                    message = messages.next() => match message {
+                        Some(message) => {
+                            expiry = message.expiry;
+                            message.text
+                        }
+                        None => break,
+                    },
+                    () = expired => {
+                        expiry = None;
+                        String::new()
+                    }
+                };
+                yield Ok(self.texts(text));
+            }
+        };
+        Ok(Box::pin(stream))
+    }
+}
+
+// Sets the text of the `IpcText` widgets called `name`, clearing it after
+// `ttl` if given.
+//
+// The text is kept for widgets that start later, so it isn't lost if the
+// widgets are being restarted.
+pub(crate) fn set(name: &str, text: String, ttl: Option<Duration>) -> Result<()> {
+    let message = Message {
+        text,
+        expiry: ttl.map(|ttl| Instant::now() + ttl),
+    };
+    let mut widgets = WIDGETS.lock().unwrap();
+    let widgets = widgets
+        .get_mut(name)
+        .ok_or_else(|| anyhow!("There is no ipc_text widget called {name}"))?;
+    // Widgets that have stopped are forgotten.
+    widgets
+        .senders
+        .retain(|sender| sender.unbounded_send(message.clone()).is_ok());
+    widgets.last = Some(message);
+    Ok(())
+}
+
+// Forgets the names of widgets that are no longer running, so that setting
+// their text is an error again, e.g. after the config has been reloaded
+// without them.
+//
+// Widgets that are waiting to be restarted aren't running either, so this is
+// only called once a new set of widgets has been started.
+pub(crate) fn forget_stopped() {
+    let mut widgets = WIDGETS.lock().unwrap();
+    widgets.retain(|_, widgets| {
+        widgets.senders.retain(|sender| !sender.is_closed());
+        !widgets.senders.is_empty()
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::{forget_stopped, set, IpcText};
+    use crate::text::{Attributes, Color, Font, Padding, VerticalAlignment};
+    use crate::widget::Widget;
+    use futures::StreamExt;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn set_with_ttl() {
+        let attr = Attributes {
+            font: Font::new("monospace 11"),
+            fg_color: Color::white(),
+            bg_color: None,
+            padding: Padding::new(0.0, 0.0, 0.0, 0.0),
+            valign: VerticalAlignment::Center,
+        };
+        let widget = IpcText::new(attr.clone(), "test".to_owned()).with_text("Initial".to_owned());
+        let mut stream = Box::new(widget).into_stream().unwrap();
+        let texts = stream.next().await.unwrap().unwrap();
+        assert_eq!(texts[0].text, "Initial");
+
+        set("test", "Hello".to_owned(), Some(Duration::from_millis(10))).unwrap();
+        let texts = stream.next().await.unwrap().unwrap();
+        assert_eq!(texts[0].text, "Hello");
+        // The text is cleared once its TTL has passed.
+        assert!(stream.next().await.unwrap().unwrap().is_empty());
+
+        // A widget started later shows the most recent text.
+        set("test", "Again".to_owned(), None).unwrap();
+        let widget = IpcText::new(attr, "test".to_owned()).with_text("Initial".to_owned());
+        let mut restarted = Box::new(widget).into_stream().unwrap();
+        let texts = restarted.next().await.unwrap().unwrap();
+        assert_eq!(texts[0].text, "Again");
+
+        assert!(set("missing", String::new(), None).is_err());
+    }
+
+    #[tokio::test]
+    async fn set_after_removal() {
+        let attr = Attributes {
+            font: Font::new("monospace 11"),
+            fg_color: Color::white(),
+            bg_color: None,
+            padding: Padding::new(0.0, 0.0, 0.0, 0.0),
+            valign: VerticalAlignment::Center,
+        };
+        let widget = IpcText::new(attr, "removed".to_owned());
+        let stream = Box::new(widget).into_stream().unwrap();
+        set("removed", "Hello".to_owned(), None).unwrap();
+
+        // The name is kept while the widget may still be restarted.
+        drop(stream);
+        set("removed", "Again".to_owned(), None).unwrap();
+        forget_stopped();
+        assert!(set("removed", String::new(), None).is_err());
+    }
+}
@@ -0,0 +1,745 @@
+// Layout of the widgets' texts on a bar.
+//
+// This is kept separate from `Bar` so that it doesn't need a surface to draw
+// on (or an X server), and can be unit tested.
+
+use ordered_float::OrderedFloat;
+
+use crate::bar::Alignment;
+use crate::text::ComputedText;
+
+// The texts of a single widget, along with the zone they are laid out in.
+#[derive(Debug)]
+pub(crate) struct Content {
+    pub alignment: Alignment,
+    pub texts: Vec<ComputedText>,
+    // How far across the bar the broadest of the texts reached as computed,
+    // before they were all made as broad as the bar.
+    pub breadth: f64,
+}
+
+// Whether the texts of a bar are laid out side by side (on a top or bottom
+// bar) or stacked on top of each other (on a left or right bar).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Orientation {
+    Horizontal,
+    Vertical,
+}
+
+impl Orientation {
+    // The size of `text` along the bar.
+    fn length(self, text: &ComputedText) -> f64 {
+        match self {
+            Orientation::Horizontal => text.width,
+            Orientation::Vertical => text.height,
+        }
+    }
+
+    fn set_length(self, text: &mut ComputedText, length: f64) {
+        match self {
+            Orientation::Horizontal => text.width = length,
+            Orientation::Vertical => text.height = length,
+        }
+    }
+
+    // The size of `text` across the bar.
+    fn breadth(self, text: &ComputedText) -> f64 {
+        match self {
+            Orientation::Horizontal => text.height,
+            Orientation::Vertical => text.width,
+        }
+    }
+
+    fn set_breadth(self, text: &mut ComputedText, breadth: f64) {
+        match self {
+            Orientation::Horizontal => text.height = breadth,
+            Orientation::Vertical => text.width = breadth,
+        }
+    }
+
+    // Moves `text` to `position` along the bar.
+    fn set_position(self, text: &mut ComputedText, position: f64) {
+        match self {
+            Orientation::Horizontal => text.x = position,
+            Orientation::Vertical => text.y = position,
+        }
+    }
+}
+
+// What needs to be redrawn after a widget's content changes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Redraw {
+    // Only the widget itself, which kept its previous geometry.
+    Content,
+    // Every widget, as they have all been laid out again. The bar is now
+    // `breadth` pixels across (i.e. tall, for a horizontal bar).
+    Bar { breadth: f64 },
+}
+
+// Differences in size smaller than this are ignored.
+const ERROR_MARGIN: f64 = f64::EPSILON;
+
+fn zone(contents: &[Content], alignment: Alignment) -> impl Iterator<Item = &ComputedText> {
+    contents
+        .iter()
+        .filter(move |content| content.alignment == alignment)
+        .flat_map(|content| content.texts.iter())
+}
+
+fn zone_mut(
+    contents: &mut [Content],
+    alignment: Alignment,
+) -> impl Iterator<Item = &mut ComputedText> {
+    contents
+        .iter_mut()
+        .filter(move |content| content.alignment == alignment)
+        .flat_map(|content| content.texts.iter_mut())
+}
+
+// Returns the breadth of the broadest of `texts`, or 0 if there are none.
+fn broadest(texts: &[ComputedText], orientation: Orientation) -> f64 {
+    texts
+        .iter()
+        .map(|text| orientation.breadth(text))
+        .max_by_key(|breadth| OrderedFloat(*breadth))
+        .unwrap_or(0.0)
+}
+
+// Whether replacing the texts of the widget at `idx` with `new` (as freshly
+// computed by `Text::compute()`, and `breadth` across) moves any other text,
+// in which case the whole bar needs to be laid out again.
+//
+// That is the case if the number of texts changed, if a non-stretch text
+// changed length, or if the bar changes breadth (unless it has a fixed
+// breadth): either a new text no longer fits across it, or the widget was the
+// broadest and has shrunk. Stretch texts take whatever space is left over, so
+// their computed length doesn't matter.
+fn needs_layout(
+    contents: &[Content],
+    idx: usize,
+    new: &[ComputedText],
+    breadth: f64,
+    orientation: Orientation,
+    fixed_breadth: Option<f64>,
+) -> bool {
+    let old = &contents[idx].texts;
+    let resized = old.len() != new.len()
+        || old.iter().zip(new).any(|(old, new)| {
+            let length_change = (orientation.length(old) - orientation.length(new)).abs();
+            !new.stretch && length_change >= ERROR_MARGIN
+        });
+    if resized || fixed_breadth.is_some() {
+        return resized;
+    }
+
+    // The bar is as broad as the broadest widget.
+    let others = contents
+        .iter()
+        .enumerate()
+        .filter(|&(other, _)| other != idx)
+        .map(|(_, content)| content.breadth)
+        .fold(0.0, f64::max);
+    let old_breadth = others.max(contents[idx].breadth);
+    (old_breadth - others.max(breadth)).abs() >= ERROR_MARGIN
+}
+
+// Replaces the texts of the widget at `idx` with `new`, for a bar that is
+// `length` pixels long (and `fixed_breadth` pixels across, if set).
+//
+// If no other text needs to move, the new texts take over the geometry of the
+// old ones. Otherwise, every text is laid out again.
+pub(crate) fn update_content(
+    contents: &mut [Content],
+    idx: usize,
+    mut new: Vec<ComputedText>,
+    length: f64,
+    orientation: Orientation,
+    fixed_breadth: Option<f64>,
+) -> Redraw {
+    let breadth = broadest(&new, orientation);
+    if needs_layout(contents, idx, &new, breadth, orientation, fixed_breadth) {
+        contents[idx].texts = new;
+        contents[idx].breadth = breadth;
+        let breadth = layout(contents, length, orientation, fixed_breadth);
+        return Redraw::Bar { breadth };
+    }
+
+    let old = &contents[idx].texts;
+    for (new, old) in new.iter_mut().zip(old) {
+        new.x = old.x;
+        new.y = old.y;
+        orientation.set_breadth(new, orientation.breadth(old));
+        // Only use length for stretch widgets.
+        if new.stretch {
+            orientation.set_length(new, orientation.length(old));
+        }
+    }
+    contents[idx].texts = new;
+    contents[idx].breadth = breadth;
+    Redraw::Content
+}
+
+// Lays out every text on a bar that is `length` pixels long, returning the
+// breadth of the bar.
+//
+// Each text is as broad as the bar. That is `fixed_breadth` if set, or else
+// the breadth of the broadest text, i.e. on a horizontal bar every text is as
+// tall as the tallest one. The left zone starts at the start of the
+// bar (its left or top edge), the right zone ends at the end of the bar and
+// the center zone is centered on the bar. Stretch texts share the space left
+// over.
+pub(crate) fn layout(
+    contents: &mut [Content],
+    length: f64,
+    orientation: Orientation,
+    fixed_breadth: Option<f64>,
+) -> f64 {
+    // Set the breadth to the max breadth of any content.
+    let breadth = fixed_breadth.unwrap_or_else(|| {
+        contents
+            .iter()
+            .map(|content| content.breadth)
+            .max_by_key(|breadth| OrderedFloat(*breadth))
+            .unwrap_or(0.0)
+    });
+    for text in contents
+        .iter_mut()
+        .flat_map(|content| content.texts.iter_mut())
+    {
+        orientation.set_breadth(text, breadth);
+    }
+
+    let fixed_length = |alignment| -> f64 {
+        zone(contents, alignment)
+            .filter(|text| !text.stretch)
+            .map(|text| orientation.length(text))
+            .sum()
+    };
+    let stretches_count = |alignment| -> usize {
+        zone(contents, alignment)
+            .filter(|text| text.stretch)
+            .count()
+    };
+    let left_fixed = fixed_length(Alignment::Left);
+    let center_fixed = fixed_length(Alignment::Center);
+    let right_fixed = fixed_length(Alignment::Right);
+    let left_stretches = stretches_count(Alignment::Left);
+    let center_stretches = stretches_count(Alignment::Center);
+    let right_stretches = stretches_count(Alignment::Right);
+    let has_center = zone(contents, Alignment::Center).next().is_some();
+
+    // Work out how much space is left over for the stretch texts in each
+    // zone. Without a center zone, the stretch texts on either side share
+    // whatever the non-stretch texts leave free. With a center zone, each
+    // side only gets the space between its own texts and the centered
+    // texts, and any stretch texts in the center grow evenly into both.
+    let share = |remaining: f64, count: usize| -> f64 {
+        if count == 0 {
+            0.0
+        } else {
+            remaining.max(0.0) / (count as f64)
+        }
+    };
+    let (left_stretch, center_stretch, right_stretch) = if !has_center {
+        let remaining = length - left_fixed - right_fixed;
+        let stretch = share(remaining, left_stretches + right_stretches);
+        (stretch, 0.0, stretch)
+    } else {
+        let side = (length - center_fixed) / 2.0;
+        let mut left_gap = (side - left_fixed).max(0.0);
+        let mut right_gap = (side - right_fixed).max(0.0);
+        let mut center_stretch = 0.0;
+        if center_stretches > 0 {
+            let grow = left_gap.min(right_gap);
+            center_stretch = share(grow * 2.0, center_stretches);
+            left_gap -= grow;
+            right_gap -= grow;
+        }
+        (
+            share(left_gap, left_stretches),
+            center_stretch,
+            share(right_gap, right_stretches),
+        )
+    };
+
+    // Set the position of each text based on the computed lengths.
+    let zones = [
+        (Alignment::Left, left_stretch),
+        (Alignment::Center, center_stretch),
+        (Alignment::Right, right_stretch),
+    ];
+    for (alignment, stretch_length) in zones {
+        let mut zone_length = 0.0;
+        for text in zone_mut(contents, alignment) {
+            if text.stretch {
+                orientation.set_length(text, stretch_length);
+            }
+            zone_length += orientation.length(text);
+        }
+
+        let mut position = match alignment {
+            Alignment::Left => 0.0,
+            Alignment::Center => (length - zone_length) / 2.0,
+            Alignment::Right => length - zone_length,
+        };
+        for text in zone_mut(contents, alignment) {
+            orientation.set_position(text, position);
+            position += orientation.length(text);
+        }
+    }
+
+    breadth
+}
+
+// Finds the text under the point (`x`, `y`) of a bar laid out by `layout()`.
+//
+// Returns the index of the widget, the index of the text within the widget
+// and the text itself.
+pub(crate) fn hit_test(
+    contents: &[Content],
+    x: f64,
+    y: f64,
+) -> Option<(usize, usize, &ComputedText)> {
+    contents
+        .iter()
+        .enumerate()
+        .flat_map(|(idx, content)| {
+            content
+                .texts
+                .iter()
+                .enumerate()
+                .map(move |(index, text)| (idx, index, text))
+        })
+        .find(|(_, _, text)| {
+            x >= text.x && x < text.x + text.width && y >= text.y && y < text.y + text.height
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{broadest, hit_test, layout, update_content, Content, Orientation, Redraw};
+    use crate::bar::Alignment;
+    use crate::text::{Attributes, Color, ComputedText, Font, Padding, VerticalAlignment};
+
+    const WIDTH: f64 = 100.0;
+    const HORIZONTAL: Orientation = Orientation::Horizontal;
+
+    fn text(width: f64, height: f64) -> ComputedText {
+        ComputedText {
+            attr: Attributes {
+                font: Font::new("monospace 11"),
+                fg_color: Color::white(),
+                bg_color: None,
+                padding: Padding::new(0.0, 0.0, 0.0, 0.0),
+                valign: VerticalAlignment::Center,
+            },
+            text: String::new(),
+            stretch: false,
+            x: 0.0,
+            y: 0.0,
+            width,
+            height,
+            markup: false,
+        }
+    }
+
+    fn stretch(width: f64, height: f64) -> ComputedText {
+        ComputedText {
+            stretch: true,
+            ..text(width, height)
+        }
+    }
+
+    fn content(alignment: Alignment, texts: Vec<ComputedText>) -> Content {
+        Content {
+            alignment,
+            breadth: broadest(&texts, HORIZONTAL),
+            texts,
+        }
+    }
+
+    fn vertical_content(alignment: Alignment, texts: Vec<ComputedText>) -> Content {
+        Content {
+            alignment,
+            breadth: broadest(&texts, Orientation::Vertical),
+            texts,
+        }
+    }
+
+    // Returns the (x, width) of each text of each widget.
+    fn geometry(contents: &[Content]) -> Vec<Vec<(f64, f64)>> {
+        contents
+            .iter()
+            .map(|content| {
+                content
+                    .texts
+                    .iter()
+                    .map(|text| (text.x, text.width))
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Returns the widget and text indices of the text under (`x`, `y`), and
+    // the point relative to the text.
+    fn hit(contents: &[Content], x: f64, y: f64) -> Option<(usize, usize, f64, f64)> {
+        hit_test(contents, x, y).map(|(idx, index, text)| (idx, index, x - text.x, y - text.y))
+    }
+
+    // Lays out `contents`, as if they had been added to a bar one by one.
+    fn laid_out(mut contents: Vec<Content>) -> Vec<Content> {
+        layout(&mut contents, WIDTH, HORIZONTAL, None);
+        contents
+    }
+
+    #[test]
+    fn zones() {
+        let mut contents = vec![
+            content(Alignment::Left, vec![text(10.0, 10.0), text(20.0, 10.0)]),
+            content(Alignment::Right, vec![text(15.0, 10.0)]),
+            content(Alignment::Center, vec![text(30.0, 10.0)]),
+        ];
+        let height = layout(&mut contents, WIDTH, HORIZONTAL, None);
+        assert_eq!(height, 10.0);
+        assert_eq!(
+            geometry(&contents),
+            vec![
+                vec![(0.0, 10.0), (10.0, 20.0)],
+                vec![(85.0, 15.0)],
+                vec![(35.0, 30.0)],
+            ]
+        );
+    }
+
+    #[test]
+    fn vertical() {
+        let mut contents = vec![
+            vertical_content(Alignment::Left, vec![text(30.0, 10.0), stretch(0.0, 10.0)]),
+            vertical_content(Alignment::Center, vec![text(20.0, 20.0)]),
+            vertical_content(Alignment::Right, vec![text(40.0, 10.0)]),
+        ];
+        // The bar is as wide as the widest text, and texts are stacked from
+        // the top to the bottom.
+        let width = layout(&mut contents, WIDTH, Orientation::Vertical, None);
+        assert_eq!(width, 40.0);
+        let geometry: Vec<Vec<_>> = contents
+            .iter()
+            .map(|content| {
+                content
+                    .texts
+                    .iter()
+                    .map(|text| (text.y, text.width, text.height))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            geometry,
+            vec![
+                vec![(0.0, 40.0, 10.0), (10.0, 40.0, 30.0)],
+                vec![(40.0, 40.0, 20.0)],
+                vec![(90.0, 40.0, 10.0)],
+            ]
+        );
+
+        // Growing wider than the bar means laying it out again.
+        let redraw = update_content(
+            &mut contents,
+            1,
+            vec![text(50.0, 20.0)],
+            WIDTH,
+            Orientation::Vertical,
+            None,
+        );
+        assert_eq!(redraw, Redraw::Bar { breadth: 50.0 });
+    }
+
+    #[test]
+    fn heights() {
+        let mut contents = vec![
+            content(Alignment::Left, vec![text(10.0, 12.0)]),
+            content(Alignment::Right, vec![text(10.0, 20.0)]),
+        ];
+        assert_eq!(layout(&mut contents, WIDTH, HORIZONTAL, None), 20.0);
+        assert!(contents
+            .iter()
+            .flat_map(|content| &content.texts)
+            .all(|text| text.height == 20.0));
+
+        assert_eq!(layout(&mut [], WIDTH, HORIZONTAL, None), 0.0);
+    }
+
+    #[test]
+    fn fixed_height() {
+        let mut contents = vec![
+            content(Alignment::Left, vec![text(10.0, 12.0)]),
+            content(Alignment::Right, vec![text(10.0, 20.0)]),
+        ];
+        // Every text is as tall as the bar, even if that cuts some off.
+        assert_eq!(layout(&mut contents, WIDTH, HORIZONTAL, Some(16.0)), 16.0);
+        assert!(contents
+            .iter()
+            .flat_map(|content| &content.texts)
+            .all(|text| text.height == 16.0));
+
+        // So a taller text doesn't change the bar.
+        let redraw = update_content(
+            &mut contents,
+            0,
+            vec![text(10.0, 30.0)],
+            WIDTH,
+            HORIZONTAL,
+            Some(16.0),
+        );
+        assert_eq!(redraw, Redraw::Content);
+        assert_eq!(contents[0].texts[0].height, 16.0);
+    }
+
+    #[test]
+    fn stretches() {
+        let mut contents = vec![
+            content(Alignment::Left, vec![text(10.0, 10.0), stretch(0.0, 10.0)]),
+            content(Alignment::Right, vec![stretch(0.0, 10.0), text(20.0, 10.0)]),
+        ];
+        layout(&mut contents, WIDTH, HORIZONTAL, None);
+        assert_eq!(
+            geometry(&contents),
+            vec![
+                vec![(0.0, 10.0), (10.0, 35.0)],
+                vec![(45.0, 35.0), (80.0, 20.0)],
+            ]
+        );
+    }
+
+    #[test]
+    fn center_stretches() {
+        let mut contents = vec![
+            content(Alignment::Left, vec![text(10.0, 10.0)]),
+            content(Alignment::Center, vec![stretch(0.0, 10.0)]),
+            content(Alignment::Right, vec![text(30.0, 10.0)]),
+        ];
+        layout(&mut contents, WIDTH, HORIZONTAL, None);
+        // The center can only grow as far as the widest side allows, while
+        // staying centered.
+        assert_eq!(
+            geometry(&contents),
+            vec![vec![(0.0, 10.0)], vec![(30.0, 40.0)], vec![(70.0, 30.0)]]
+        );
+    }
+
+    #[test]
+    fn overflow() {
+        let mut contents = vec![
+            content(Alignment::Left, vec![text(80.0, 10.0), stretch(0.0, 10.0)]),
+            content(Alignment::Right, vec![text(40.0, 10.0)]),
+        ];
+        layout(&mut contents, WIDTH, HORIZONTAL, None);
+        // Stretch texts never get a negative width.
+        assert_eq!(
+            geometry(&contents),
+            vec![vec![(0.0, 80.0), (80.0, 0.0)], vec![(60.0, 40.0)]]
+        );
+    }
+
+    #[test]
+    fn same_width() {
+        let mut contents = laid_out(vec![
+            content(Alignment::Left, vec![text(10.0, 10.0)]),
+            content(Alignment::Right, vec![text(20.0, 10.0)]),
+        ]);
+        let redraw = update_content(
+            &mut contents,
+            1,
+            vec![text(20.0, 10.0)],
+            WIDTH,
+            HORIZONTAL,
+            None,
+        );
+        assert_eq!(redraw, Redraw::Content);
+        assert_eq!(
+            geometry(&contents),
+            vec![vec![(0.0, 10.0)], vec![(80.0, 20.0)]]
+        );
+    }
+
+    #[test]
+    fn width_grows() {
+        let mut contents = laid_out(vec![
+            content(Alignment::Left, vec![text(10.0, 10.0), text(20.0, 10.0)]),
+            content(Alignment::Right, vec![text(20.0, 10.0)]),
+        ]);
+        let redraw = update_content(
+            &mut contents,
+            0,
+            vec![text(15.0, 10.0), text(20.0, 10.0)],
+            WIDTH,
+            HORIZONTAL,
+            None,
+        );
+        assert_eq!(redraw, Redraw::Bar { breadth: 10.0 });
+        assert_eq!(
+            geometry(&contents),
+            vec![vec![(0.0, 15.0), (15.0, 20.0)], vec![(80.0, 20.0)]]
+        );
+    }
+
+    #[test]
+    fn width_shrinks() {
+        let mut contents = laid_out(vec![
+            content(Alignment::Left, vec![text(10.0, 10.0)]),
+            content(Alignment::Right, vec![text(20.0, 10.0), text(10.0, 10.0)]),
+        ]);
+        let redraw = update_content(
+            &mut contents,
+            1,
+            vec![text(5.0, 10.0), text(10.0, 10.0)],
+            WIDTH,
+            HORIZONTAL,
+            None,
+        );
+        assert_eq!(redraw, Redraw::Bar { breadth: 10.0 });
+        assert_eq!(
+            geometry(&contents),
+            vec![vec![(0.0, 10.0)], vec![(85.0, 5.0), (90.0, 10.0)]]
+        );
+    }
+
+    #[test]
+    fn stretch_width_is_kept() {
+        let mut contents = laid_out(vec![
+            content(Alignment::Left, vec![text(10.0, 10.0), stretch(5.0, 10.0)]),
+            content(Alignment::Right, vec![text(20.0, 10.0)]),
+        ]);
+        // The computed width of a stretch text is irrelevant, as it takes
+        // the left over space.
+        let redraw = update_content(
+            &mut contents,
+            0,
+            vec![text(10.0, 10.0), stretch(25.0, 10.0)],
+            WIDTH,
+            HORIZONTAL,
+            None,
+        );
+        assert_eq!(redraw, Redraw::Content);
+        assert_eq!(
+            geometry(&contents),
+            vec![vec![(0.0, 10.0), (10.0, 70.0)], vec![(80.0, 20.0)]]
+        );
+    }
+
+    #[test]
+    fn text_count_changes() {
+        let mut contents = laid_out(vec![
+            content(Alignment::Left, vec![text(10.0, 10.0)]),
+            content(Alignment::Left, vec![text(20.0, 10.0)]),
+        ]);
+
+        let redraw = update_content(
+            &mut contents,
+            0,
+            vec![text(10.0, 10.0), text(10.0, 10.0)],
+            WIDTH,
+            HORIZONTAL,
+            None,
+        );
+        assert_eq!(redraw, Redraw::Bar { breadth: 10.0 });
+        assert_eq!(
+            geometry(&contents),
+            vec![vec![(0.0, 10.0), (10.0, 10.0)], vec![(20.0, 20.0)]]
+        );
+
+        let redraw = update_content(&mut contents, 0, Vec::new(), WIDTH, HORIZONTAL, None);
+        assert_eq!(redraw, Redraw::Bar { breadth: 10.0 });
+        assert_eq!(geometry(&contents), vec![vec![], vec![(0.0, 20.0)]]);
+    }
+
+    #[test]
+    fn text_grows_taller() {
+        let mut contents = laid_out(vec![
+            content(Alignment::Left, vec![text(10.0, 10.0)]),
+            content(Alignment::Right, vec![text(20.0, 10.0)]),
+        ]);
+
+        let redraw = update_content(
+            &mut contents,
+            0,
+            vec![text(10.0, 14.0)],
+            WIDTH,
+            HORIZONTAL,
+            None,
+        );
+        assert_eq!(redraw, Redraw::Bar { breadth: 14.0 });
+        assert_eq!(contents[1].texts[0].height, 14.0);
+
+        // Shorter texts are simply drawn at the height of the bar.
+        let redraw = update_content(
+            &mut contents,
+            1,
+            vec![text(20.0, 8.0)],
+            WIDTH,
+            HORIZONTAL,
+            None,
+        );
+        assert_eq!(redraw, Redraw::Content);
+        assert_eq!(contents[1].texts[0].height, 14.0);
+    }
+
+    #[test]
+    fn tallest_text_shrinks() {
+        let mut contents = laid_out(vec![
+            content(Alignment::Left, vec![text(10.0, 14.0)]),
+            content(Alignment::Right, vec![text(20.0, 10.0)]),
+        ]);
+
+        // The bar shrinks to fit the texts that are left.
+        let redraw = update_content(
+            &mut contents,
+            0,
+            vec![text(10.0, 8.0)],
+            WIDTH,
+            HORIZONTAL,
+            None,
+        );
+        assert_eq!(redraw, Redraw::Bar { breadth: 10.0 });
+        assert_eq!(contents[0].texts[0].height, 10.0);
+        assert_eq!(contents[1].texts[0].height, 10.0);
+
+        // Unless it has a fixed breadth.
+        let redraw = update_content(
+            &mut contents,
+            1,
+            vec![text(20.0, 6.0)],
+            WIDTH,
+            HORIZONTAL,
+            Some(10.0),
+        );
+        assert_eq!(redraw, Redraw::Content);
+        assert_eq!(contents[1].texts[0].height, 10.0);
+    }
+
+    #[test]
+    fn hit_texts() {
+        let contents = laid_out(vec![
+            content(Alignment::Left, vec![text(10.0, 10.0), text(20.0, 10.0)]),
+            content(Alignment::Right, vec![stretch(0.0, 10.0), text(20.0, 10.0)]),
+        ]);
+
+        assert_eq!(hit(&contents, 5.0, 4.0), Some((0, 0, 5.0, 4.0)));
+        // A boundary belongs to the text that starts there.
+        assert_eq!(hit(&contents, 10.0, 0.0), Some((0, 1, 0.0, 0.0)));
+        // The stretched text fills the space between the two zones.
+        assert_eq!(hit(&contents, 45.0, 9.0), Some((1, 0, 15.0, 9.0)));
+        assert_eq!(hit(&contents, 100.0, 5.0), None);
+        assert_eq!(hit(&contents, 50.0, 10.0), None);
+    }
+
+    #[test]
+    fn hit_gap() {
+        let contents = laid_out(vec![
+            content(Alignment::Left, vec![text(10.0, 10.0)]),
+            content(Alignment::Right, vec![text(20.0, 10.0)]),
+        ]);
+
+        assert_eq!(hit(&contents, 50.0, 5.0), None);
+        assert_eq!(hit(&contents, 80.0, 5.0), Some((1, 0, 0.0, 5.0)));
+    }
+}
@@ -1,8 +1,13 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use crate::mouse::{MouseButton, MouseEvent, MouseEventKind, MouseHandler};
 use crate::text::{Attributes, Text};
 use crate::widget::{Widget, WidgetStream};
+use log::warn;
 use process_stream::{Process, ProcessExt, StreamExt};
 use serde_derive::Deserialize;
+use std::cell::Cell;
+use std::rc::Rc;
+use tokio::task;
 
 #[derive(Deserialize, Debug)]
 struct State {
@@ -48,9 +53,15 @@ pub struct LeftWMAttributes {
 }
 
 /// LeftWM widget that shows information about the worksapces and tags
+///
+/// Clicking on a tag shows it on the widget's workspace, and scrolling over
+/// the widget focuses the next or previous tag.
 pub struct LeftWM {
     output: String,
     attrs: LeftWMAttributes,
+    // The index of the workspace on `output`, as of the last state update.
+    // Shared with the mouse handler.
+    workspace: Rc<Cell<Option<usize>>>,
 }
 
 impl LeftWM {
@@ -80,6 +91,7 @@ impl LeftWM {
     ///     fg_color: Color::white(),
     ///     bg_color: Some(Color::blue()),
     ///     padding: Padding::new(8.0, 8.0, 0.0, 0.0),
+    ///     valign: VerticalAlignment::Center,
     /// };
     ///
     /// let empty = Attributes {
@@ -109,13 +121,21 @@ impl LeftWM {
     /// # fn main() { run().unwrap(); }
     /// ```
     pub fn new(output: String, attrs: LeftWMAttributes) -> Self {
-        LeftWM { output, attrs }
+        LeftWM {
+            output,
+            attrs,
+            workspace: Rc::new(Cell::new(None)),
+        }
     }
 
     fn on_change(&self, content: String) -> Result<Vec<Text>> {
         let state: State = serde_json::from_str(&content)?;
-        let w = state.workspaces.iter().find(|w| w.output == self.output);
-        if let Some(w) = w {
+        let idx = state
+            .workspaces
+            .iter()
+            .position(|w| w.output == self.output);
+        self.workspace.set(idx);
+        if let Some(w) = idx.map(|idx| &state.workspaces[idx]) {
             let text = w
                 .tags
                 .iter()
@@ -144,7 +164,44 @@ impl LeftWM {
     }
 }
 
+fn on_mouse_event(workspace: Option<usize>, event: MouseEvent) -> Result<()> {
+    if event.kind != MouseEventKind::Press {
+        return Ok(());
+    }
+
+    // Tags are rendered in order, so the index of the clicked text is the
+    // index of the tag.
+    let command = match (event.button, workspace) {
+        (MouseButton::Left, Some(workspace)) => {
+            format!("SendWorkspaceToTag {workspace} {}", event.index)
+        }
+        (MouseButton::ScrollUp, _) => "FocusPreviousTag".to_owned(),
+        (MouseButton::ScrollDown, _) => "FocusNextTag".to_owned(),
+        _ => return Ok(()),
+    };
+    let mut child = tokio::process::Command::new("leftwm-command")
+        .arg(&command)
+        .spawn()
+        .context("Failed to run `leftwm-command`")?;
+    // Reap the child once it exits, rather than leaving a zombie behind.
+    task::spawn_local(async move {
+        match child.wait().await {
+            Ok(status) if status.success() => {}
+            Ok(status) => warn!("`leftwm-command {command}` exited with {status}"),
+            Err(err) => warn!("Failed to wait for `leftwm-command {command}`: {err}"),
+        }
+    });
+    Ok(())
+}
+
 impl Widget for LeftWM {
+    fn mouse_handler(&mut self) -> Option<MouseHandler> {
+        let workspace = self.workspace.clone();
+        Some(Box::new(move |event| {
+            on_mouse_event(workspace.get(), event)
+        }))
+    }
+
     fn into_stream(self: Box<Self>) -> Result<WidgetStream> {
         let mut state = Process::new("leftwm-state");
         let s = state
@@ -8,7 +8,25 @@ pub mod disk_usage;
 pub mod active_window_title;
 pub mod volume;
 pub mod wireless;
+pub mod weather;
 pub mod sensors;
+pub mod tray;
+pub mod backend;
 pub mod bar;
+mod bars;
+mod layout;
 pub mod xcb;
 pub mod command;
+pub mod config;
+pub mod i3bar;
+pub mod ipc;
+pub mod ipc_text;
+pub mod mouse;
+pub mod output;
+pub mod randr;
+mod logging;
+mod process;
+mod schedule;
+mod signal;
+mod supervisor;
+mod watch;
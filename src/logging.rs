@@ -0,0 +1,118 @@
+// Helpers for logging what the bar and its widgets are up to.
+//
+// All logging goes through the `log` crate, so it's up to the binary to
+// decide where it ends up (see `main.rs`).
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// How long an error that keeps recurring is kept quiet for.
+const REPEAT_INTERVAL: Duration = Duration::from_secs(60);
+
+// Returns the log target for the widget called `name` at `idx`, e.g.
+// `widget::clock::2`.
+//
+// Targets are matched by prefix, so `RUST_LOG=widget::clock=debug` selects
+// every clock widget.
+pub(crate) fn widget_target(name: &str, idx: usize) -> String {
+    format!("widget::{name}::{idx}")
+}
+
+// The most recent error logged for a key.
+struct Repeated {
+    message: String,
+    logged_at: Instant,
+    // How many times the message has been suppressed since it was logged.
+    suppressed: usize,
+}
+
+// Keeps quiet about an error that's the same as the previous one for the
+// same key.
+//
+// A widget that fails on every update (e.g. because a sensor is missing)
+// would otherwise log the same error every second or so. A repeated error is
+// logged again once `REPEAT_INTERVAL` has passed, along with how many times
+// it occurred in the meantime.
+pub(crate) struct RateLimiter<K> {
+    last: HashMap<K, Repeated>,
+}
+
+impl<K: std::hash::Hash + Eq> RateLimiter<K> {
+    pub fn new() -> RateLimiter<K> {
+        RateLimiter {
+            last: HashMap::new(),
+        }
+    }
+
+    // Returns what to log for `message` about `key`: either the message
+    // itself, or nothing if it was the last message for `key` and that was
+    // logged recently.
+    pub fn filter(&mut self, key: K, message: String) -> Option<String> {
+        self.filter_at(key, message, Instant::now())
+    }
+
+    fn filter_at(&mut self, key: K, message: String, now: Instant) -> Option<String> {
+        if let Some(last) = self.last.get_mut(&key) {
+            if last.message == message {
+                if now.duration_since(last.logged_at) < REPEAT_INTERVAL {
+                    last.suppressed += 1;
+                    return None;
+                }
+                let count = last.suppressed + 1;
+                last.logged_at = now;
+                last.suppressed = 0;
+                if count == 1 {
+                    return Some(message);
+                }
+                return Some(format!("{message} (repeated {count} times)"));
+            }
+        }
+
+        let repeated = Repeated {
+            message: message.clone(),
+            logged_at: now,
+            suppressed: 0,
+        };
+        self.last.insert(key, repeated);
+        Some(message)
+    }
+
+    // Forgets the last message logged for `key`, e.g. once the widget it's
+    // for has recovered.
+    pub fn reset(&mut self, key: &K) {
+        self.last.remove(key);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{RateLimiter, REPEAT_INTERVAL};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn repeated_errors() {
+        let mut limiter = RateLimiter::new();
+        let start = Instant::now();
+        let error = || "No such sensor".to_owned();
+
+        assert_eq!(limiter.filter_at(0, error(), start), Some(error()));
+        let soon = start + Duration::from_secs(1);
+        assert_eq!(limiter.filter_at(0, error(), soon), None);
+        assert_eq!(limiter.filter_at(0, error(), soon), None);
+        // Other widgets, and other errors, are logged straight away.
+        assert_eq!(limiter.filter_at(1, error(), soon), Some(error()));
+        assert_eq!(
+            limiter.filter_at(1, "Timed out".to_owned(), soon),
+            Some("Timed out".to_owned())
+        );
+
+        let later = start + REPEAT_INTERVAL;
+        assert_eq!(
+            limiter.filter_at(0, error(), later),
+            Some("No such sensor (repeated 3 times)".to_owned())
+        );
+
+        limiter.reset(&0);
+        assert_eq!(limiter.filter_at(0, error(), later), Some(error()));
+    }
+}
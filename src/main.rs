@@ -1,117 +1,145 @@
-use rusty_bar::clock::Clock;
-use rusty_bar::battery::{Battery,BatteryInfo,Status};
-use rusty_bar::cpu::Cpu;
-use rusty_bar::active_window_title::ActiveWindowTitle;
-use rusty_bar::leftwm::{LeftWM,LeftWMAttributes};
-use rusty_bar::disk_usage::{DiskUsage,DiskInfo};
-use rusty_bar::text::{Font,Color,Attributes,Padding,Threshold};
-use rusty_bar::wireless::Wireless;
-use rusty_bar::sensors::Sensors;
-use rusty_bar::volume::Volume;
-use rusty_bar::widget::Cnx;
-use rusty_bar::bar::Position;
-use anyhow::Result;
-
-
-
-fn template(icon:String,info:String) -> String{
-    let c1="#00ee00";
-    let c2="#eeeeee";
-    format!("<span foreground=\"{c1}\">{icon}</span><span foreground=\"{c2}\">{info}</span>")
+use anyhow::{anyhow, Result};
+use log::{debug, error, LevelFilter};
+use rusty_bar::config::{self, Config};
+use rusty_bar::ipc::{self, Request, Response};
+use rusty_bar::output::OutputFormat;
+use rusty_bar::widget::DEFAULT_NAME;
+use rusty_bar::xcb::ConnectionLost;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process;
+
+const USAGE: &str = "Usage: rusty-bar [-v | -q]... [--config <path>] [--render-once <out.png>]
+                 [--output <i3bar | lemonbar | plain>]
+       rusty-bar msg [--name <name>] <message>
+
+Messages: list, refresh <index or name>, hide, show, toggle, reload,
+          set <ipc_text name> <text> [--ttl <seconds>]";
+
+// The exit status when the connection to the X server is lost, so that
+// whatever started the bar can tell that apart from other errors (e.g. to
+// restart it). This is EX_TEMPFAIL from sysexits.h.
+const EXIT_CONNECTION_LOST: i32 = 75;
+
+// Logs to stderr. Each -v shows more and each -q shows less, while RUST_LOG
+// can set the level of particular targets, e.g. `RUST_LOG=widget::clock=debug`
+// for the clock widgets.
+fn init_logging(verbosity: i32) {
+    let level = match verbosity {
+        i32::MIN..=-3 => LevelFilter::Off,
+        -2 => LevelFilter::Error,
+        -1 => LevelFilter::Warn,
+        0 => LevelFilter::Info,
+        1 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    env_logger::Builder::new()
+        .filter_level(level)
+        .parse_env("RUST_LOG")
+        .init();
 }
 
-
-
-fn attr() -> Attributes {
-    Attributes{
-	font: Font::new("Hack Nerd Font 11"),
-	fg_color: Color::from_hex("#eeeeee"),
-	bg_color: None,
-	padding: Padding::new(8.0, 8.0, 0.0, 0.0),
+// Sends a message to a running bar, e.g. `rusty-bar msg refresh volume`, and
+// prints the response.
+fn msg(args: &[String]) -> Result<()> {
+    let (name, args) = match args {
+        [flag, name, args @ ..] if flag == "-n" || flag == "--name" => (name.as_str(), args),
+        _ => (DEFAULT_NAME, args),
+    };
+    let request = Request::from_args(args).map_err(|err| anyhow!("{err}\n{USAGE}"))?;
+    match ipc::send(name, &request)? {
+        Response::Ok => {}
+        Response::Widgets(widgets) => {
+            // One line per widget: its index, name, texts and any error.
+            for widget in widgets {
+                let texts = widget.texts.join(" ");
+                let error = widget
+                    .error
+                    .map_or(String::new(), |error| format!("\t{error}"));
+                println!("{}\t{}\t{texts}{error}", widget.index, widget.name);
+            }
+        }
+        Response::Error(message) => return Err(anyhow!(message)),
     }
+    Ok(())
 }
 
-
-
 fn main() -> Result<()> {
-    let focused = Attributes {
-        fg_color: Color::from_hex("#55ff55"),
-	padding: Padding::new(8.0, 8.0, 0.0, 0.0),
-	bg_color: Some(Color::from_hex("#222222")),
-	 ..attr()
-        };
-    let visible = Attributes {
-        fg_color: Color::green(),
-        ..attr()
-    };
-    let busy = Attributes {
-	fg_color: Color::from_hex("#119911"),
-	padding: Padding::new(1.0, 1.0, 0.0, 0.0),
-	..attr()
-    };
-    let empty = Attributes {
-	fg_color: Color::from_hex("#bbbbbb"),
-	padding: Padding::new(1.0, 1.0, 0.0, 0.0),
-	..attr()
-    };
-    
-    let pager = LeftWM::new("eDP-1".to_string(),LeftWMAttributes {focused,visible,busy,empty});
-
-
-    let battery_render = Box::new(|battery_info: BatteryInfo| {
-        let percentage = battery_info.capacity;
-	let mut icon="";
-	match battery_info.status {
-	    Status::Full=> icon="  ",
-	    Status::Charging=> icon="  ",
-	    Status::Discharging=> icon="  ",
-	    Status::Unknown=> icon="  ",
-	}
-	
-        let default_text = format!("{percentage:.0}%");
-	template(String::from(icon),default_text)
-   });
-
-    let battery = Battery::new(attr(), Color::red(), Some(String::from("BAT1")), Some(battery_render));
-
-   
-    let root_render=Box::new(|disk_info: DiskInfo| {
-        let left = (disk_info.used.get_bytes()*100)/(disk_info.total.get_bytes());
-        let disk_text = format!("{left}%");
-        template(String::from(" "), disk_text)
-    });
-    let root = DiskUsage::new(attr(), String::from("/"), Some(root_render)); 
-
-
-    let home_render=Box::new(|disk_info: DiskInfo| {
-        let left = (disk_info.used.get_bytes()*100)/(disk_info.total.get_bytes());
-        let disk_text = format!("{left}%");
-        template(String::from(" "), disk_text)
-    });
-    let home = DiskUsage::new(attr(), String::from("/home"), Some(home_render)); 
-
-
-    let cpu_render = Box::new(|load| {
-	
-        template(String::from(" "),format!("{load:.0}%"))
-    });
-    let cpu = Cpu::new(attr(), Some(cpu_render))?;
-    
-
-    let mut cnx = Cnx::new(Position::Top);
-    
-    cnx.add_widget(pager);
-    cnx.add_widget(ActiveWindowTitle::new(attr()));
-    cnx.add_widget(cpu);
-    cnx.add_widget(root);
-    cnx.add_widget(home);
-    cnx.add_widget(Wireless::new(attr(),String::from("wlan0"),Some(Threshold::default())));
-    cnx.add_widget(Sensors::new(attr(),vec!["Package id 0"]));
-    cnx.add_widget(Volume::new(attr()));
-    cnx.add_widget(battery);
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("msg") {
+        return msg(&args[1..]);
+    }
 
-    cnx.add_widget(Clock::new(attr(),Some(String::from("%d-%m-%Y %a %H:%M"))));
-    cnx.run()?;
+    let mut config_path = None;
+    let mut render_path = None;
+    let mut output_format = None;
+    let mut verbosity = 0;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-c" | "--config" => {
+                let path = args.next().ok_or_else(|| anyhow!("{USAGE}"))?;
+                config_path = Some(PathBuf::from(path));
+            }
+            "--render-once" => {
+                let path = args.next().ok_or_else(|| anyhow!("{USAGE}"))?;
+                render_path = Some(PathBuf::from(path));
+            }
+            "--output" => {
+                let format = args.next().ok_or_else(|| anyhow!("{USAGE}"))?;
+                output_format = Some(format.parse::<OutputFormat>()?);
+            }
+            "-v" | "--verbose" => verbosity += 1,
+            "-q" | "--quiet" => verbosity -= 1,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            arg => match arg.strip_prefix("--output=") {
+                Some(format) => output_format = Some(format.parse::<OutputFormat>()?),
+                None => return Err(anyhow!("Unknown argument: {arg}\n{USAGE}")),
+            },
+        }
+    }
+    init_logging(verbosity);
+
+    // An explicitly given config file must exist, but we fall back to the
+    // example configuration if there's nothing at the default path. The
+    // default path is still watched if its directory exists, so that a config
+    // file created there is picked up.
+    let cnx = match config_path {
+        Some(path) => Config::load(&path)?.into_cnx()?.with_config_path(path),
+        None => match config::default_path() {
+            Some(path) if path.exists() => Config::load(&path)?.into_cnx()?.with_config_path(path),
+            Some(path) => {
+                let cnx = Config::parse(config::DEFAULT_CONFIG)?.into_cnx()?;
+                if path.parent().map_or(false, Path::is_dir) {
+                    cnx.with_config_path(path)
+                } else {
+                    debug!(
+                        "Not watching {}, as its directory doesn't exist",
+                        path.display()
+                    );
+                    cnx
+                }
+            }
+            None => Config::parse(config::DEFAULT_CONFIG)?.into_cnx()?,
+        },
+    };
 
-    Ok(())
+    // Draw a single frame instead of running the bar, e.g. for screenshots,
+    // or write the widgets' content to stdout for another bar to show.
+    let result = match (render_path, output_format) {
+        (Some(path), _) => cnx.render_once(&path),
+        (None, Some(format)) => cnx.run_headless(format),
+        (None, None) => cnx.run(),
+    };
+    if let Err(err) = &result {
+        if let Some(lost) = err.downcast_ref::<ConnectionLost>() {
+            error!("{lost}");
+            process::exit(EXIT_CONNECTION_LOST);
+        }
     }
+
+    result
+}
@@ -0,0 +1,157 @@
+//! Types to represent mouse events delivered to widgets.
+//!
+//! Widgets which want to react to clicks or scrolling return a
+//! [`MouseHandler`] from [`Widget::mouse_handler()`].
+//!
+//! [`Widget::mouse_handler()`]: ../widget/trait.Widget.html#method.mouse_handler
+
+use anyhow::Result;
+
+/// The handler returned by [`Widget::mouse_handler()`].
+///
+/// It is called with every mouse event that lands on one of the widget's
+/// texts. Any errors are logged but do not affect the runtime of the bar.
+///
+/// [`Widget::mouse_handler()`]: ../widget/trait.Widget.html#method.mouse_handler
+pub type MouseHandler = Box<dyn FnMut(MouseEvent) -> Result<()>>;
+
+/// Whether a mouse button was pressed or released.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MouseEventKind {
+    Press,
+    Release,
+}
+
+/// The mouse button an event was generated by.
+///
+/// X11 reports scrolling as presses of buttons 4 to 7, so these are mapped to
+/// the `Scroll*` variants.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    ScrollUp,
+    ScrollDown,
+    ScrollLeft,
+    ScrollRight,
+    Other(u8),
+}
+
+impl MouseButton {
+    /// Returns the X11 button number of this button.
+    pub fn code(&self) -> u8 {
+        match self {
+            MouseButton::Left => 1,
+            MouseButton::Middle => 2,
+            MouseButton::Right => 3,
+            MouseButton::ScrollUp => 4,
+            MouseButton::ScrollDown => 5,
+            MouseButton::ScrollLeft => 6,
+            MouseButton::ScrollRight => 7,
+            MouseButton::Other(code) => *code,
+        }
+    }
+}
+
+impl From<u8> for MouseButton {
+    fn from(code: u8) -> Self {
+        match code {
+            1 => MouseButton::Left,
+            2 => MouseButton::Middle,
+            3 => MouseButton::Right,
+            4 => MouseButton::ScrollUp,
+            5 => MouseButton::ScrollDown,
+            6 => MouseButton::ScrollLeft,
+            7 => MouseButton::ScrollRight,
+            code => MouseButton::Other(code),
+        }
+    }
+}
+
+/// The keyboard modifiers held down when a mouse event was generated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub control: bool,
+    /// Usually the `Alt` key (X11's `Mod1`).
+    pub alt: bool,
+    /// Usually the `Super`/`Windows` key (X11's `Mod4`).
+    pub super_key: bool,
+}
+
+impl Modifiers {
+    /// Decodes the `state` mask of an X11 button event.
+    pub fn from_state(state: u16) -> Self {
+        let state = u32::from(state);
+        Modifiers {
+            shift: state & xcb::MOD_MASK_SHIFT != 0,
+            control: state & xcb::MOD_MASK_CONTROL != 0,
+            alt: state & xcb::MOD_MASK_1 != 0,
+            super_key: state & xcb::MOD_MASK_4 != 0,
+        }
+    }
+}
+
+/// A mouse event on one of a widget's texts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MouseEvent {
+    pub kind: MouseEventKind,
+    pub button: MouseButton,
+    pub modifiers: Modifiers,
+    /// The index of the text that was clicked, within the `Vec<Text>` most
+    /// recently yielded by the widget.
+    pub index: usize,
+    /// The position of the pointer, relative to the top-left corner of the
+    /// text that was clicked.
+    pub x: f64,
+    pub y: f64,
+    /// The position of the pointer on the screen.
+    pub root_x: f64,
+    pub root_y: f64,
+    /// The size of the text that was clicked.
+    pub width: f64,
+    pub height: f64,
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Modifiers, MouseButton};
+
+    #[test]
+    fn buttons() {
+        assert_eq!(MouseButton::from(1), MouseButton::Left);
+        assert_eq!(MouseButton::from(3), MouseButton::Right);
+        assert_eq!(MouseButton::from(4), MouseButton::ScrollUp);
+        assert_eq!(MouseButton::from(7), MouseButton::ScrollRight);
+        assert_eq!(MouseButton::from(9), MouseButton::Other(9));
+        for code in 1..=9 {
+            assert_eq!(MouseButton::from(code).code(), code);
+        }
+    }
+
+    #[test]
+    fn modifiers() {
+        assert_eq!(Modifiers::from_state(0), Modifiers::default());
+        let shift_alt = (xcb::MOD_MASK_SHIFT | xcb::MOD_MASK_1) as u16;
+        assert_eq!(
+            Modifiers::from_state(shift_alt),
+            Modifiers {
+                shift: true,
+                alt: true,
+                ..Modifiers::default()
+            }
+        );
+        // Button masks and locks are ignored.
+        let state = (xcb::MOD_MASK_CONTROL | xcb::MOD_MASK_4 | xcb::MOD_MASK_LOCK) as u16
+            | xcb::BUTTON_MASK_1 as u16;
+        assert_eq!(
+            Modifiers::from_state(state),
+            Modifiers {
+                control: true,
+                super_key: true,
+                ..Modifiers::default()
+            }
+        );
+    }
+}
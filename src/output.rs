@@ -0,0 +1,346 @@
+//! Writing the content of the widgets to stdout, rather than showing it on a
+//! bar.
+//!
+//! See [`Cnx::run_headless()`], which makes the widgets usable as the status
+//! command of another bar, like swaybar or lemonbar.
+//!
+//! [`Cnx::run_headless()`]: ../widget/struct.Cnx.html#method.run_headless
+
+use anyhow::{anyhow, Error, Result};
+use serde_derive::Serialize;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::bar::Alignment;
+use crate::bars::BarOptions;
+use crate::text::{Color, Text};
+use crate::widget::ErrorDisplay;
+
+// Put between widgets by the formats that don't have blocks of their own.
+const SEPARATOR: &str = " | ";
+
+/// The format in which [`Cnx::run_headless()`] writes the content of the
+/// widgets.
+///
+/// [`Cnx::run_headless()`]: ../widget/struct.Cnx.html#method.run_headless
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// The i3bar protocol, which swaybar also speaks. Each text is a block,
+    /// keeping its colors and Pango markup.
+    I3bar,
+    /// Lemonbar's input format, with the widgets placed in the zones they're
+    /// aligned to and colored with `%{F}` and `%{B}`.
+    Lemonbar,
+    /// Plain text, without any colors or markup.
+    Plain,
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(format: &str) -> Result<Self> {
+        match format {
+            "i3bar" => Ok(OutputFormat::I3bar),
+            "lemonbar" => Ok(OutputFormat::Lemonbar),
+            "plain" => Ok(OutputFormat::Plain),
+            _ => Err(anyhow!("Unknown output format: {format}")),
+        }
+    }
+}
+
+// Where a running `Cnx` shows the content of its widgets: either its `Bars`,
+// or a `Printer` when running headless.
+pub(crate) trait Output {
+    // Replaces the options and widgets shown, e.g. after the config file has
+    // been reloaded.
+    fn reconfigure(&mut self, options: BarOptions, alignments: Vec<Alignment>) -> Result<()>;
+
+    // Returns the most recent content of the widget at `idx`.
+    fn content(&self, idx: usize) -> &[Text];
+
+    // Updates the content of the widget at `idx`.
+    fn update_content(&mut self, idx: usize, content: Vec<Text>) -> Result<()>;
+
+    // Shows that the widget at `idx` has failed with the given error, if the
+    // options say how to.
+    fn show_error(&mut self, idx: usize, error: &str) -> Result<()>;
+
+    // Shows or hides the content.
+    fn set_visible(&mut self, visible: bool) -> Result<()>;
+
+    fn visible(&self) -> bool;
+}
+
+// Keeps the most recent content of each widget, to be written to stdout in
+// the given format.
+pub(crate) struct Printer {
+    format: OutputFormat,
+    error_display: Option<ErrorDisplay>,
+    alignments: Vec<Alignment>,
+    contents: Vec<Vec<Text>>,
+    // Whether the content has changed since it was last written.
+    changed: bool,
+}
+
+impl Printer {
+    pub fn new(format: OutputFormat, options: BarOptions, alignments: Vec<Alignment>) -> Printer {
+        Printer {
+            format,
+            error_display: options.error_display,
+            contents: vec![Vec::new(); alignments.len()],
+            alignments,
+            changed: false,
+        }
+    }
+
+    // Writes what the format needs before the first line of content.
+    pub fn write_header(&self, out: &mut impl Write) -> io::Result<()> {
+        if self.format == OutputFormat::I3bar {
+            writeln!(out, r#"{{"version":1}}"#)?;
+            writeln!(out, "[")?;
+        }
+        out.flush()
+    }
+
+    // Writes a line with the content of every widget, if it has changed
+    // since the last one.
+    pub fn write(&mut self, out: &mut impl Write) -> io::Result<()> {
+        if !self.changed {
+            return Ok(());
+        }
+        self.changed = false;
+        let line = match self.format {
+            OutputFormat::I3bar => self.i3bar_line(),
+            OutputFormat::Lemonbar => self.lemonbar_line(),
+            OutputFormat::Plain => self.plain_line(),
+        };
+        writeln!(out, "{line}")?;
+        out.flush()
+    }
+
+    fn i3bar_line(&self) -> String {
+        let mut blocks = Vec::new();
+        for content in &self.contents {
+            for (idx, text) in content.iter().enumerate() {
+                // The texts of a widget are kept together.
+                let last = idx + 1 == content.len();
+                blocks.push(Block {
+                    full_text: &text.text,
+                    markup: if text.markup { "pango" } else { "none" },
+                    color: text.attr.fg_color.to_hex(),
+                    background: text.attr.bg_color.as_ref().map(|color| color.to_hex()),
+                    separator: last,
+                    separator_block_width: if last { None } else { Some(0) },
+                });
+            }
+        }
+        // Serializing these can't fail.
+        let blocks = serde_json::to_string(&blocks).unwrap_or_default();
+        format!("{blocks},")
+    }
+
+    fn lemonbar_line(&self) -> String {
+        let mut line = String::new();
+        for (alignment, zone) in [
+            (Alignment::Left, "%{l}"),
+            (Alignment::Center, "%{c}"),
+            (Alignment::Right, "%{r}"),
+        ] {
+            let widgets: Vec<String> = self
+                .widgets(alignment)
+                .map(|content| {
+                    let texts: Vec<String> = content.iter().map(lemonbar_text).collect();
+                    texts.join(" ")
+                })
+                .collect();
+            if !widgets.is_empty() {
+                line.push_str(zone);
+                line.push_str(&widgets.join(SEPARATOR));
+            }
+        }
+        line
+    }
+
+    fn plain_line(&self) -> String {
+        let widgets: Vec<String> = self
+            .contents
+            .iter()
+            .filter(|content| !content.is_empty())
+            .map(|content| {
+                let texts: Vec<String> = content.iter().map(plain_text).collect();
+                texts.join(" ")
+            })
+            .collect();
+        widgets.join(SEPARATOR)
+    }
+
+    // Returns the content of the widgets aligned to `alignment` that have
+    // any.
+    fn widgets(&self, alignment: Alignment) -> impl Iterator<Item = &Vec<Text>> {
+        self.contents
+            .iter()
+            .zip(&self.alignments)
+            .filter(move |(content, align)| **align == alignment && !content.is_empty())
+            .map(|(content, _)| content)
+    }
+}
+
+impl Output for Printer {
+    fn reconfigure(&mut self, options: BarOptions, alignments: Vec<Alignment>) -> Result<()> {
+        self.error_display = options.error_display;
+        self.contents = vec![Vec::new(); alignments.len()];
+        self.alignments = alignments;
+        self.changed = true;
+        Ok(())
+    }
+
+    fn content(&self, idx: usize) -> &[Text] {
+        &self.contents[idx]
+    }
+
+    fn update_content(&mut self, idx: usize, content: Vec<Text>) -> Result<()> {
+        if self.contents[idx] != content {
+            self.contents[idx] = content;
+            self.changed = true;
+        }
+        Ok(())
+    }
+
+    fn show_error(&mut self, idx: usize, error: &str) -> Result<()> {
+        match &self.error_display {
+            Some(display) => {
+                let text = display.text(error);
+                self.update_content(idx, vec![text])
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn set_visible(&mut self, _visible: bool) -> Result<()> {
+        Err(anyhow!("There is no bar to show or hide"))
+    }
+
+    fn visible(&self) -> bool {
+        true
+    }
+}
+
+// A block of the i3bar protocol.
+#[derive(Serialize)]
+struct Block<'a> {
+    full_text: &'a str,
+    markup: &'static str,
+    color: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    background: Option<String>,
+    separator: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    separator_block_width: Option<u32>,
+}
+
+// Returns the text without any Pango markup.
+fn plain_text(text: &Text) -> String {
+    if !text.markup {
+        return text.text.clone();
+    }
+    match pango::parse_markup(&text.text, '\0') {
+        Ok((_, plain, _)) => plain.to_string(),
+        Err(_) => text.text.clone(),
+    }
+}
+
+// Lemonbar puts the alpha of a color first, as `#AARRGGBB`, rather than last
+// like `Color::to_hex()`.
+fn lemonbar_color(color: &Color) -> String {
+    match color.to_rgba() {
+        [r, g, b, 255] => format!("#{r:02X}{g:02X}{b:02X}"),
+        [r, g, b, a] => format!("#{a:02X}{r:02X}{g:02X}{b:02X}"),
+    }
+}
+
+fn lemonbar_text(text: &Text) -> String {
+    // Lemonbar reads `%{` as the start of a command.
+    let plain = plain_text(text).replace('%', "%%");
+    match &text.attr.bg_color {
+        Some(bg_color) => format!(
+            "%{{F{}}}%{{B{}}}{plain}%{{B-}}%{{F-}}",
+            lemonbar_color(&text.attr.fg_color),
+            lemonbar_color(bg_color)
+        ),
+        None => format!(
+            "%{{F{}}}{plain}%{{F-}}",
+            lemonbar_color(&text.attr.fg_color)
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{lemonbar_color, Output, OutputFormat, Printer};
+    use crate::bar::{Alignment, Offset, Position};
+    use crate::bars::{BarOptions, Placement};
+    use crate::text::{Attributes, Color, Font, Padding, Text, VerticalAlignment};
+
+    fn text(text: &str) -> Text {
+        Text {
+            attr: Attributes {
+                font: Font::new("monospace 11"),
+                fg_color: Color::white(),
+                bg_color: None,
+                padding: Padding::new(0.0, 0.0, 0.0, 0.0),
+                valign: VerticalAlignment::Center,
+            },
+            text: text.to_owned(),
+            stretch: false,
+            markup: true,
+        }
+    }
+
+    fn printer(format: OutputFormat) -> Printer {
+        let options = BarOptions {
+            position: Position::Top,
+            placement: Placement::Single {
+                width: None,
+                offset: Offset::default(),
+            },
+            name: "rusty-bar".to_owned(),
+            height: None,
+            error_display: None,
+        };
+        let mut printer = Printer::new(format, options, vec![Alignment::Left, Alignment::Right]);
+        printer
+            .update_content(0, vec![text("<b>1</b>"), text("2")])
+            .unwrap();
+        printer.update_content(1, vec![text("100%")]).unwrap();
+        printer
+    }
+
+    fn output(mut printer: Printer) -> String {
+        let mut out = Vec::new();
+        printer.write(&mut out).unwrap();
+        // Nothing is written until the content changes.
+        printer.write(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!(output(printer(OutputFormat::Plain)), "1 2 | 100%\n");
+        assert_eq!(
+            output(printer(OutputFormat::Lemonbar)),
+            "%{l}%{F#FFFFFF}1%{F-} %{F#FFFFFF}2%{F-}%{r}%{F#FFFFFF}100%%%{F-}\n"
+        );
+        let blocks = output(printer(OutputFormat::I3bar));
+        assert!(blocks.starts_with(r##"[{"full_text":"<b>1</b>","markup":"pango","color":"#FFFFFF","separator":false,"separator_block_width":0},"##));
+        assert!(blocks.ends_with("],\n"));
+    }
+
+    #[test]
+    fn lemonbar_alpha() {
+        assert_eq!(lemonbar_color(&Color::from_rgb(30, 30, 46)), "#1E1E2E");
+        assert_eq!(
+            lemonbar_color(&Color::from_rgba(30, 30, 46, 128)),
+            "#801E1E2E"
+        );
+    }
+}
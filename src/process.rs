@@ -0,0 +1,103 @@
+// Runs the programs that some widgets get their content from.
+//
+// Everything runs on a single thread, so a widget mustn't wait for a program
+// to finish by blocking: that would freeze the whole bar until it did.
+
+use anyhow::{anyhow, Context, Result};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::time;
+
+// How long a program may run for, if its widget doesn't say.
+pub(crate) const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// How long a program may run for, and what happens to it if it runs for
+// longer.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Timeout {
+    pub duration: Duration,
+    // Whether to kill the program, rather than leave it running in the
+    // background, once it has timed out.
+    pub kill: bool,
+}
+
+impl Default for Timeout {
+    fn default() -> Timeout {
+        Timeout {
+            duration: DEFAULT_TIMEOUT,
+            kill: true,
+        }
+    }
+}
+
+// Runs `command` and returns what it writes to stdout.
+//
+// It's an error if the program can't be started, exits unsuccessfully (in
+// which case the error includes what it wrote to stderr) or runs for longer
+// than `timeout`.
+pub(crate) async fn output(command: &mut Command, timeout: Timeout) -> Result<String> {
+    let child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(timeout.kill)
+        .spawn()
+        .context("Failed to start")?;
+
+    // Timing out drops the child, which kills it if `timeout.kill` is set.
+    let output = match time::timeout(timeout.duration, child.wait_with_output()).await {
+        Ok(output) => output.context("Failed to read output")?,
+        Err(_) => {
+            let action = if timeout.kill {
+                "killed"
+            } else {
+                "left running"
+            };
+            return Err(anyhow!(
+                "Timed out after {}s, {action}",
+                timeout.duration.as_secs_f64()
+            ));
+        }
+    };
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return match stderr.trim() {
+            "" => Err(anyhow!("Exited with {}", output.status)),
+            stderr => Err(anyhow!("Exited with {}: {stderr}", output.status)),
+        };
+    }
+    String::from_utf8(output.stdout).context("Invalid UTF-8 in output")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{output, Timeout};
+    use std::time::Duration;
+    use tokio::process::Command;
+
+    fn sh(script: &str) -> Command {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script);
+        command
+    }
+
+    #[tokio::test]
+    async fn runs_programs() {
+        let timeout = Timeout::default();
+        assert_eq!(output(&mut sh("echo foo"), timeout).await.unwrap(), "foo\n");
+
+        let err = output(&mut sh("echo oops >&2; exit 3"), timeout)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Exited with exit status: 3: oops");
+
+        let timeout = Timeout {
+            duration: Duration::from_millis(10),
+            kill: true,
+        };
+        let err = output(&mut sh("sleep 5"), timeout).await.unwrap_err();
+        assert_eq!(err.to_string(), "Timed out after 0.01s, killed");
+    }
+}
@@ -0,0 +1,185 @@
+//! Discovery of monitors using the RandR extension.
+
+use anyhow::{anyhow, Context, Result};
+use xcb::randr;
+
+use crate::bar::Rect;
+
+/// An enum specifying which monitors to show a bar on.
+///
+/// Passed to [`Cnx::with_monitors()`] to show one bar on each selected
+/// monitor. Bars are created, moved and destroyed as monitors are connected,
+/// rearranged and disconnected.
+///
+/// [`Cnx::with_monitors()`]: ../widget/struct.Cnx.html#method.with_monitors
+///
+/// # Examples
+///
+/// ```
+/// # use rusty_bar::bar::Position;
+/// # use rusty_bar::randr::Monitors;
+/// # use rusty_bar::widget::Cnx;
+/// let cnx = Cnx::new(Position::Top).with_monitors(Monitors::Named(vec!["eDP-1".into()]));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Monitors {
+    /// Show a bar on every connected monitor.
+    All,
+    /// Show a bar on each of the named outputs (e.g. `eDP-1`), if connected.
+    Named(Vec<String>),
+}
+
+impl Monitors {
+    // Picks the selected monitors out of `monitors`.
+    //
+    // Outputs that mirror each other show the same area (often through the
+    // same CRTC), so only the first of them is kept rather than stacking a
+    // bar for each in the same place.
+    fn select(&self, monitors: Vec<Monitor>) -> Vec<Monitor> {
+        let selected: Vec<Monitor> = match self {
+            Monitors::All => monitors,
+            Monitors::Named(names) => names
+                .iter()
+                .filter_map(|name| monitors.iter().find(|m| &m.name == name).cloned())
+                .collect(),
+        };
+        let mut distinct: Vec<Monitor> = Vec::with_capacity(selected.len());
+        for monitor in selected {
+            if !distinct.iter().any(|m| m.area == monitor.area) {
+                distinct.push(monitor);
+            }
+        }
+        distinct
+    }
+}
+
+/// A connected and enabled RandR output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Monitor {
+    /// The name of the output, e.g. `eDP-1`.
+    pub name: String,
+    /// The area of the root window the output shows.
+    pub area: Rect,
+}
+
+// Returns all connected and enabled outputs of the screen with the given
+// `root` window.
+pub fn monitors(conn: &xcb::Connection, root: xcb::Window) -> Result<Vec<Monitor>> {
+    let resources = randr::get_screen_resources_current(conn, root)
+        .get_reply()
+        .context("Failed to get RandR screen resources")?;
+    let timestamp = resources.config_timestamp();
+
+    let mut monitors = Vec::new();
+    for &output in resources.outputs() {
+        let info = randr::get_output_info(conn, output, timestamp)
+            .get_reply()
+            .context("Failed to get RandR output info")?;
+        // Disconnected outputs and outputs which are connected but
+        // disabled have no CRTC.
+        if u32::from(info.connection()) != randr::CONNECTION_CONNECTED || info.crtc() == xcb::NONE {
+            continue;
+        }
+
+        let crtc = randr::get_crtc_info(conn, info.crtc(), timestamp)
+            .get_reply()
+            .context("Failed to get RandR CRTC info")?;
+        monitors.push(Monitor {
+            name: String::from_utf8_lossy(info.name()).into_owned(),
+            area: Rect {
+                x: crtc.x(),
+                y: crtc.y(),
+                width: crtc.width(),
+                height: crtc.height(),
+            },
+        });
+    }
+
+    Ok(monitors)
+}
+
+// Returns the outputs of the screen selected by `selection`.
+pub fn select_monitors(
+    conn: &xcb::Connection,
+    root: xcb::Window,
+    selection: &Monitors,
+) -> Result<Vec<Monitor>> {
+    Ok(selection.select(monitors(conn, root)?))
+}
+
+// Registers for RandR notifications on the `root` window, so that we hear
+// about monitors being connected, disconnected or rearranged.
+//
+// Returns the first event code of the RandR extension, which must be passed
+// to `is_change_event()`.
+pub fn select_input(conn: &xcb::Connection, root: xcb::Window) -> Result<u8> {
+    let extension = xcb::query_extension(conn, "RANDR")
+        .get_reply()
+        .context("Failed to query RandR extension")?;
+    if !extension.present() {
+        return Err(anyhow!("The X server does not support the RandR extension"));
+    }
+
+    let mask = randr::NOTIFY_MASK_SCREEN_CHANGE
+        | randr::NOTIFY_MASK_CRTC_CHANGE
+        | randr::NOTIFY_MASK_OUTPUT_CHANGE;
+    randr::select_input(conn, root, mask as u16);
+    conn.flush();
+
+    Ok(extension.first_event())
+}
+
+// Whether `event` is a RandR notification that the monitor layout changed.
+pub fn is_change_event(event: &xcb::GenericEvent, first_event: u8) -> bool {
+    let response_type = (event.response_type() & !0x80).wrapping_sub(first_event);
+    response_type == randr::SCREEN_CHANGE_NOTIFY || response_type == randr::NOTIFY
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Monitor, Monitors};
+    use crate::bar::Rect;
+
+    fn monitor(name: &str, x: i16) -> Monitor {
+        Monitor {
+            name: name.to_owned(),
+            area: Rect {
+                x,
+                y: 0,
+                width: 1920,
+                height: 1080,
+            },
+        }
+    }
+
+    fn names(monitors: Vec<Monitor>) -> Vec<String> {
+        monitors.into_iter().map(|m| m.name).collect()
+    }
+
+    #[test]
+    fn select() {
+        let monitors = vec![monitor("eDP-1", 0), monitor("HDMI-1", 1920)];
+        assert_eq!(
+            names(Monitors::All.select(monitors.clone())),
+            ["eDP-1", "HDMI-1"]
+        );
+
+        // Named outputs are kept in the order they're named in, and those
+        // that aren't connected are skipped.
+        let named = Monitors::Named(vec!["HDMI-1".into(), "DP-1".into(), "eDP-1".into()]);
+        assert_eq!(names(named.select(monitors)), ["HDMI-1", "eDP-1"]);
+
+        // HDMI-1 mirrors eDP-1.
+        let mirrored = vec![
+            monitor("eDP-1", 0),
+            monitor("HDMI-1", 0),
+            monitor("DP-1", 1920),
+        ];
+        assert_eq!(
+            names(Monitors::All.select(mirrored.clone())),
+            ["eDP-1", "DP-1"]
+        );
+        let named = Monitors::Named(vec!["HDMI-1".into(), "eDP-1".into()]);
+        assert_eq!(names(named.select(mirrored)), ["HDMI-1"]);
+    }
+}
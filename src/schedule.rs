@@ -0,0 +1,87 @@
+// Decides when widgets that poll for their content update.
+//
+// Rather than every widget counting its interval from whenever it happened to
+// start, ticks are aligned to the local wall clock: a widget with an interval
+// of a minute updates on the minute, and one with an interval of ten seconds
+// at :00, :10, :20 and so on. That way the clock changes when the minute does,
+// and widgets with the same (or a multiple of the same) interval wake up
+// together, rather than each waking the CPU at a different time.
+
+use chrono::{DateTime, Local, Offset, TimeZone};
+use futures::stream::{self, Stream, StreamExt};
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::time;
+
+// The longest time to sleep for without checking the wall clock again, so
+// that ticks still happen on time after the system has been suspended (during
+// which sleeps are paused) or the clock has been changed.
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+pub(crate) type Ticks = Pin<Box<dyn Stream<Item = ()>>>;
+
+// Returns a stream which yields once straight away, and then each time the
+// wall clock reaches a multiple of `interval`.
+//
+// Ticks that are missed because the stream wasn't polled in time (e.g. as the
+// widget was busy) are skipped rather than delivered late.
+pub(crate) fn ticks(interval: Duration) -> Ticks {
+    let first = next_tick(&Local::now(), interval);
+    let rest = stream::unfold(first, move |tick| async move {
+        wait_until(&tick).await;
+        let next = next_tick(&tick.max(Local::now()), interval);
+        Some(((), next))
+    });
+    Box::pin(stream::once(async {}).chain(rest))
+}
+
+// Returns the first multiple of `interval` in the local time of `after`
+// which is later than `after`.
+fn next_tick<Tz: TimeZone>(after: &DateTime<Tz>, interval: Duration) -> DateTime<Tz> {
+    let interval = i64::try_from(interval.as_millis())
+        .unwrap_or(i64::MAX)
+        .max(1);
+    let offset = i64::from(after.offset().fix().local_minus_utc()) * 1000;
+    let local = after.timestamp_millis() + offset;
+    let next = (local.div_euclid(interval) + 1).saturating_mul(interval);
+    after.clone() + chrono::Duration::milliseconds(next - local)
+}
+
+async fn wait_until(tick: &DateTime<Local>) {
+    while let Ok(remaining) = (*tick - Local::now()).to_std() {
+        if remaining.is_zero() {
+            break;
+        }
+        time::sleep(remaining.min(MAX_SLEEP)).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::next_tick;
+    use chrono::{DateTime, FixedOffset, TimeZone};
+    use std::time::Duration;
+
+    fn time(hour: u32, min: u32, sec: u32) -> DateTime<FixedOffset> {
+        // UTC+05:30, so that local hours aren't UTC hours.
+        let offset = FixedOffset::east_opt(5 * 3600 + 30 * 60).unwrap();
+        offset.with_ymd_and_hms(2022, 3, 1, hour, min, sec).unwrap()
+    }
+
+    #[test]
+    fn aligned_to_local_time() {
+        let minute = Duration::from_secs(60);
+        assert_eq!(next_tick(&time(12, 0, 1), minute), time(12, 1, 0));
+        // A tick that's exactly on time is followed by the next one.
+        assert_eq!(next_tick(&time(12, 1, 0), minute), time(12, 2, 0));
+
+        let hour = Duration::from_secs(3600);
+        assert_eq!(next_tick(&time(12, 30, 0), hour), time(13, 0, 0));
+
+        let ten_seconds = Duration::from_secs(10);
+        assert_eq!(
+            next_tick(&time(23, 59, 55), ten_seconds),
+            time(0, 0, 0) + chrono::Duration::days(1)
+        );
+    }
+}
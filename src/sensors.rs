@@ -1,13 +1,14 @@
 use anyhow::{anyhow, Context, Result};
+use crate::process::{self, Timeout};
+use crate::schedule;
 use crate::text::{Attributes, Text};
 use crate::widget::{Widget, WidgetStream};
+use futures::StreamExt;
 use regex::Regex;
 use std::collections::HashMap;
-use std::process::Command;
+use std::rc::Rc;
 use std::time::Duration;
-use tokio::time;
-use tokio_stream::wrappers::IntervalStream;
-use tokio_stream::StreamExt;
+use tokio::process::Command;
 
 #[derive(Debug, PartialEq)]
 struct Value<'a> {
@@ -51,6 +52,7 @@ pub struct Sensors {
     update_interval: Duration,
     attr: Attributes,
     sensors: Vec<String>,
+    timeout: Timeout,
 }
 
 impl Sensors {
@@ -85,6 +87,7 @@ impl Sensors {
     ///     fg_color: Color::white(),
     ///     bg_color: None,
     ///     padding: Padding::new(8.0, 8.0, 0.0, 0.0),
+    ///     valign: VerticalAlignment::Center,
     /// };
     ///
     /// let mut cnx = Cnx::new(Position::Top);
@@ -100,15 +103,43 @@ impl Sensors {
             update_interval: Duration::from_secs(60),
             attr,
             sensors: sensors.into_iter().map(Into::into).collect(),
+            timeout: Timeout::default(),
         }
     }
 
-    fn tick(&self) -> Result<Vec<Text>> {
-        let output = Command::new("sensors")
-            .output()
+    /// Sets how often the temperatures are updated. The default is a minute.
+    pub fn with_interval(self, update_interval: Duration) -> Self {
+        Self {
+            update_interval,
+            ..self
+        }
+    }
+
+    /// Sets how long `sensors` may run for before the widget gives up on it.
+    /// The default is ten seconds.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        let timeout = Timeout {
+            duration: timeout,
+            ..self.timeout
+        };
+        Self { timeout, ..self }
+    }
+
+    /// Sets whether `sensors` is killed once it has timed out (the default),
+    /// or left running in the background.
+    pub fn with_kill_on_timeout(self, kill: bool) -> Self {
+        let timeout = Timeout {
+            kill,
+            ..self.timeout
+        };
+        Self { timeout, ..self }
+    }
+
+    async fn tick(&self) -> Result<Vec<Text>> {
+        let output = process::output(&mut Command::new("sensors"), self.timeout)
+            .await
             .context("Failed to run `sensors`")?;
-        let string = String::from_utf8(output.stdout).context("Invalid UTF-8 in sensors output")?;
-        let parsed = parse_sensors_output(&string).context("Failed to parse `sensors` output")?;
+        let parsed = parse_sensors_output(&output).context("Failed to parse `sensors` output")?;
         self.sensors
             .iter()
             .map(|sensor_name| {
@@ -130,8 +161,12 @@ impl Sensors {
 
 impl Widget for Sensors {
     fn into_stream(self: Box<Self>) -> Result<WidgetStream> {
-        let interval = time::interval(self.update_interval);
-        let stream = IntervalStream::new(interval).map(move |_| self.tick());
+        let ticks = schedule::ticks(self.update_interval);
+        let widget: Rc<Sensors> = Rc::from(self);
+        let stream = ticks.then(move |_| {
+            let widget = widget.clone();
+            async move { widget.tick().await }
+        });
 
         Ok(Box::pin(stream))
     }
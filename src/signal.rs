@@ -0,0 +1,43 @@
+// Refreshes widgets when the bar receives a real-time signal, as i3blocks
+// does: a widget bound to signal `n` is refreshed on SIGRTMIN+n, e.g. after
+// `pkill -RTMIN+3 rusty-bar` in a key binding that changes the volume.
+
+use anyhow::{anyhow, Context, Result};
+use futures::stream::{self, Stream};
+use nix::libc;
+use std::pin::Pin;
+use tokio::signal::unix::{self, SignalKind};
+
+pub(crate) type Signals = Pin<Box<dyn Stream<Item = ()>>>;
+
+// Returns a stream which yields each time the bar receives SIGRTMIN+`offset`.
+//
+// Once this has been called, that signal no longer terminates the bar, even
+// after the stream has been dropped.
+pub(crate) fn realtime(offset: u32) -> Result<Signals> {
+    let max = libc::SIGRTMAX() - libc::SIGRTMIN();
+    let signal = i32::try_from(offset)
+        .ok()
+        .filter(|&offset| offset <= max)
+        .ok_or_else(|| anyhow!("SIGRTMIN+{offset} doesn't exist, the last is SIGRTMIN+{max}"))?;
+    let mut signal = unix::signal(SignalKind::from_raw(libc::SIGRTMIN() + signal))
+        .with_context(|| format!("Failed to listen for SIGRTMIN+{offset}"))?;
+    Ok(Box::pin(stream::poll_fn(move |cx| signal.poll_recv(cx))))
+}
+
+#[cfg(test)]
+mod test {
+    use super::realtime;
+    use futures::StreamExt;
+    use nix::libc;
+
+    #[tokio::test]
+    async fn receives_signals() {
+        let mut signals = realtime(3).unwrap();
+        // nix's `raise()` doesn't cover real-time signals.
+        assert_eq!(unsafe { libc::raise(libc::SIGRTMIN() + 3) }, 0);
+        assert_eq!(signals.next().await, Some(()));
+
+        assert!(realtime(1000).is_err());
+    }
+}
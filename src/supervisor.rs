@@ -0,0 +1,96 @@
+// Keeps a misbehaving widget from taking the rest of the bar down with it.
+//
+// A panic in a widget (e.g. in one of the closures that render its text) is
+// caught and turned into an event, as is the end of its stream, so that the
+// widget can be restarted according to its `RestartPolicy`.
+
+use anyhow::{anyhow, Result};
+use futures::stream::{self, Stream, StreamExt};
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+
+use crate::text::Text;
+use crate::widget::WidgetStream;
+
+// What the stream of a running widget yields.
+pub(crate) enum WidgetEvent {
+    Update(Result<Vec<Text>>),
+    // The widget panicked with the given message, which stopped its stream.
+    Panicked(String),
+    // The widget's stream has ended.
+    Ended,
+}
+
+pub(crate) type SupervisedStream = Pin<Box<dyn Stream<Item = WidgetEvent>>>;
+
+// Wraps the stream of a widget so that it ends with either `Panicked` or
+// `Ended`, rather than taking the bar down or silently stopping.
+pub(crate) fn supervise(updates: WidgetStream) -> SupervisedStream {
+    let events = AssertUnwindSafe(updates)
+        .catch_unwind()
+        .map(|result| match result {
+            Ok(update) => WidgetEvent::Update(update),
+            Err(payload) => WidgetEvent::Panicked(panic_message(&*payload)),
+        });
+    // This yields `Ended` after `Panicked` too, but whoever runs the stream
+    // should have stopped polling it by then.
+    Box::pin(events.chain(stream::once(async { WidgetEvent::Ended })))
+}
+
+// Calls `f`, turning a panic into an error. This is for the parts of a widget
+// that are called directly rather than through its stream, like its mouse
+// handler.
+pub(crate) fn catch_panic<T>(f: impl FnOnce() -> Result<T>) -> Result<T> {
+    panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|payload| Err(anyhow!("Panicked: {}", panic_message(&*payload))))
+}
+
+// Returns the message passed to `panic!()`, if there was one.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_owned()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Unknown panic".to_owned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{catch_panic, supervise, WidgetEvent};
+    use crate::text::Text;
+    use anyhow::Result;
+    use futures::executor::block_on;
+    use futures::stream::{self, StreamExt};
+
+    #[test]
+    fn panicking_stream() {
+        let updates = stream::iter(vec![1, 2]).map(|n| -> Result<Vec<Text>> {
+            assert!(n < 2, "No more updates");
+            Ok(vec![])
+        });
+        let events: Vec<_> = block_on(supervise(Box::pin(updates)).collect());
+        assert!(matches!(events[0], WidgetEvent::Update(Ok(_))));
+        assert!(
+            matches!(&events[1], WidgetEvent::Panicked(message) if message == "No more updates")
+        );
+    }
+
+    #[test]
+    fn ended_stream() {
+        let updates: Vec<Result<Vec<Text>>> = vec![Ok(vec![])];
+        let updates = stream::iter(updates);
+        let events: Vec<_> = block_on(supervise(Box::pin(updates)).collect());
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[1], WidgetEvent::Ended));
+    }
+
+    #[test]
+    fn panicking_function() {
+        let result: Result<()> = catch_panic(|| panic!("Oops"));
+        assert_eq!(result.unwrap_err().to_string(), "Panicked: Oops");
+        assert_eq!(catch_panic(|| Ok(1)).unwrap(), 1);
+    }
+}
@@ -3,17 +3,19 @@
 //! This module is light on documentation. See the existing widget
 //! implementations for inspiration.
 
-use anyhow::Result;
-use cairo::{Context, Surface};
+use anyhow::{anyhow, Error, Result};
+use cairo::{Context, Operator, Surface};
 use colors_transform::{Color as ColorTransform, Rgb};
 use pango::{EllipsizeMode, FontDescription};
 use std::fmt;
+use std::str::FromStr;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Color {
     red: f64,
     green: f64,
     blue: f64,
+    alpha: f64,
 }
 
 macro_rules! color {
@@ -24,6 +26,7 @@ macro_rules! color {
                 red: $r,
                 green: $g,
                 blue: $b,
+                alpha: 1.0,
             }
         }
     };
@@ -37,65 +40,118 @@ impl Color {
     color!(black, (0.0, 0.0, 0.0));
     color!(yellow, (1.0, 1.0, 0.0));
 
+    /// A fully transparent color.
+    pub fn transparent() -> Color {
+        Color {
+            red: 0.0,
+            green: 0.0,
+            blue: 0.0,
+            alpha: 0.0,
+        }
+    }
+
     pub fn apply_to_context(&self, cr: &Context) {
-        cr.set_source_rgb(self.red, self.green, self.blue);
+        cr.set_source_rgba(self.red, self.green, self.blue, self.alpha);
     }
 
     pub fn from_rgb(r: u8, g: u8, b: u8) -> Self {
+        Self::from_rgba(r, g, b, 255)
+    }
+
+    pub fn from_rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
         Self {
             red: r as f64 / 255.0,
             green: g as f64 / 255.0,
             blue: b as f64 / 255.0,
+            alpha: a as f64 / 255.0,
         }
     }
 
-    /// Parse string as hex color
+    /// Parse string as hex color, either `#rgb`, `#rrggbb` or `#rrggbbaa`
     /// # Example
     /// ```
-    /// use cnx::text::Color;
+    /// use rusty_bar::text::Color;
     ///
     /// assert_eq!(Color::from_hex("#1e1e2e"), Color::from_rgb(30, 30, 46));
+    /// assert_eq!(Color::from_hex("#1e1e2e80"), Color::from_rgba(30, 30, 46, 128));
     /// assert_eq!(Color::from_hex("not hex"), Color::from_rgb(0, 0, 0));
     /// ```
     pub fn from_hex(hex: &str) -> Self {
-        let rgb = match Rgb::from_hex_str(hex) {
-            Ok(rgb) => rgb,
-            Err(_) => Rgb::from(0.0, 0.0, 0.0),
-        };
-
-        Self {
-            red: rgb.get_red() as f64 / 255.0,
-            green: rgb.get_green() as f64 / 255.0,
-            blue: rgb.get_blue() as f64 / 255.0,
-        }
+        hex.parse().unwrap_or_else(|_| Color::black())
     }
 
+    /// Format as a hex color, `#RRGGBB` if the color is opaque and
+    /// `#RRGGBBAA` otherwise, i.e. in a form that can be parsed back again.
+    /// # Example
+    /// ```
+    /// use rusty_bar::text::Color;
+    ///
+    /// assert_eq!(Color::from_rgb(30, 30, 46).to_hex(), "#1E1E2E");
+    /// assert_eq!(Color::from_rgba(30, 30, 46, 128).to_hex(), "#1E1E2E80");
+    /// ```
     pub fn to_hex(&self) -> String {
-        let r = if self.red >= 1.0 {
-            255
+        let [r, g, b, a] = self.to_rgba();
+        if a == 255 {
+            format!("#{r:02X}{g:02X}{b:02X}")
         } else {
-            (self.red * 255.0) as i32
-        };
-        let g = if self.green >= 1.0 {
-            255
-        } else {
-            (self.green * 255.0) as i32
-        };
-        let b = if self.blue >= 1.0 {
-            255
-        } else {
-            (self.blue * 255.0) as i32
+            format!("#{r:02X}{g:02X}{b:02X}{a:02X}")
+        }
+    }
+
+    // Returns the red, green, blue and alpha channels, from 0 to 255.
+    pub(crate) fn to_rgba(&self) -> [u8; 4] {
+        let channel = |value: f64| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        [
+            channel(self.red),
+            channel(self.green),
+            channel(self.blue),
+            channel(self.alpha),
+        ]
+    }
+}
+
+/// Parse string as hex color, failing if it isn't one.
+///
+/// See [`Color::from_hex()`] for a version which falls back to black.
+impl FromStr for Color {
+    type Err = Error;
+
+    fn from_str(hex: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid hex color: {}", hex);
+
+        // `Rgb` doesn't know about alpha, so split it off ourselves.
+        let (rgb, alpha) = match hex.trim().strip_prefix('#') {
+            Some(digits) if digits.len() == 8 => {
+                let alpha = digits
+                    .get(6..)
+                    .and_then(|alpha| u8::from_str_radix(alpha, 16).ok())
+                    .ok_or_else(invalid)?;
+                (digits[..6].to_owned(), alpha)
+            }
+            // `#rgb` is short for `#rrggbb`.
+            Some(digits) if digits.len() == 3 => (
+                digits.chars().flat_map(|digit| [digit, digit]).collect(),
+                255,
+            ),
+            _ => (hex.to_owned(), 255),
         };
-        format!("#{:0width$X}{:0width$X}{:0width$X}", r, g, b, width = 2)
+        let rgb = Rgb::from_hex_str(&rgb).map_err(|_| invalid())?;
+
+        Ok(Self {
+            red: rgb.get_red() as f64 / 255.0,
+            green: rgb.get_green() as f64 / 255.0,
+            blue: rgb.get_blue() as f64 / 255.0,
+            alpha: alpha as f64 / 255.0,
+        })
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Padding {
-    left: f64,
-    right: f64,
-    top: f64,
-    bottom: f64,
+    pub left: f64,
+    pub right: f64,
+    pub top: f64,
+    pub bottom: f64,
 }
 
 impl Padding {
@@ -124,12 +180,23 @@ impl fmt::Debug for Font {
     }
 }
 
+/// Where a text is placed within the height of the bar, when the bar is
+/// taller than the text.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VerticalAlignment {
+    Top,
+    #[default]
+    Center,
+    Bottom,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Attributes {
     pub font: Font,
     pub fg_color: Color,
     pub bg_color: Option<Color>,
     pub padding: Padding,
+    pub valign: VerticalAlignment,
 }
 
 pub struct PagerAttributes {
@@ -231,17 +298,31 @@ impl ComputedText {
         layout.set_width(text_width as i32 * pango::SCALE);
         layout.set_height(text_height as i32 * pango::SCALE);
 
-        let bg_color = &self.attr.bg_color.clone().unwrap_or_else(Color::black);
+        // Replace whatever was drawn here before, rather than blending with
+        // it. Without a background color, the text has a transparent
+        // background.
+        let transparent = Color::transparent();
+        let bg_color = self.attr.bg_color.as_ref().unwrap_or(&transparent);
         bg_color.apply_to_context(&context);
-        // FIXME: The use of `height` isnt' right here: we want to do the
-        // full height of the bar, not the full height of the text. It
-        // would be useful if we could do Surface.get_height(), but that
-        // doesn't seem to be available in cairo-rs for some reason?
+        context.set_operator(Operator::Source);
+        // Once laid out, texts are as tall as the bar, so this fills the
+        // full height of the bar.
         context.rectangle(0.0, 0.0, self.width, self.height);
         context.fill()?;
+        context.set_operator(Operator::Over);
+
+        // Texts in different fonts differ in height, so place each one
+        // within the space the padding leaves.
+        let (_, layout_height) = layout.pixel_size();
+        let spare = text_height - f64::from(layout_height);
+        let top = match self.attr.valign {
+            VerticalAlignment::Top => padding.top,
+            VerticalAlignment::Center => padding.top + (spare / 2.0).round(),
+            VerticalAlignment::Bottom => padding.top + spare,
+        };
 
         self.attr.fg_color.apply_to_context(&context);
-        context.translate(padding.left, padding.top);
+        context.translate(padding.left, top);
         show_pango_layout(&context, &layout);
 
         Ok(())
@@ -279,3 +360,36 @@ impl Default for Threshold {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::Color;
+
+    #[test]
+    fn parse_colors() {
+        assert_eq!(
+            "#f80".parse::<Color>().unwrap(),
+            Color::from_rgb(255, 136, 0)
+        );
+        assert_eq!(
+            "#1e1e2e".parse::<Color>().unwrap(),
+            Color::from_rgb(30, 30, 46)
+        );
+        assert_eq!(
+            "#1e1e2e80".parse::<Color>().unwrap(),
+            Color::from_rgba(30, 30, 46, 128)
+        );
+        for invalid in ["", "#", "1e1e2e80", "#12345", "#gggggg", "#1e1e2ezz"] {
+            assert!(invalid.parse::<Color>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn hex_round_trips() {
+        for hex in ["#FF8800", "#1E1E2E", "#1E1E2E80", "#00000000"] {
+            assert_eq!(hex.parse::<Color>().unwrap().to_hex(), hex);
+        }
+        assert_eq!(Color::white().to_hex(), "#FFFFFF");
+        assert_eq!(Color::transparent().to_hex(), "#00000000");
+    }
+}
@@ -0,0 +1,556 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashSet;
+use std::rc::Rc;
+use tokio_stream::{self as stream, StreamExt};
+use xcb_util::ewmh;
+
+use crate::backend::choose_visual;
+use crate::bar::Position;
+use crate::layout::Orientation;
+use crate::text::{Attributes, Padding, Text};
+use crate::widget::{Widget, WidgetStream};
+use crate::xcb::{connect, XcbEventStream};
+
+// Opcodes of `_NET_SYSTEM_TRAY_OPCODE` messages.
+const SYSTEM_TRAY_REQUEST_DOCK: u32 = 0;
+
+// Messages and flags of the XEmbed protocol.
+const XEMBED_EMBEDDED_NOTIFY: u32 = 0;
+const XEMBED_VERSION: u32 = 0;
+const XEMBED_MAPPED: u32 = 1;
+
+/// Shows a system tray, holding the icons of applications such as nm-applet.
+///
+/// This widget implements the [`System Tray`] protocol: it becomes the system
+/// tray manager of the screen and embeds each application's icon window using
+/// [`XEmbed`]. Icons are shown as squares as tall as the bar (minus the
+/// widget's top and bottom padding), and the widget is as wide as it needs to
+/// be to fit them. On a left or right bar, see [`Tray::with_position()`], the
+/// icons are as wide as the bar instead and stacked from top to bottom.
+///
+/// Only one system tray can run on a screen. If there is a bar on more than
+/// one monitor, the icons are shown on the first bar and the space for them is
+/// left empty on the others. Adding a second `Tray`, or starting another
+/// system tray later, takes the icons away from this one. There is no tray
+/// without a bar, so the widget fails to start with [`Cnx::run_headless()`]
+/// or [`Cnx::render_once()`].
+///
+/// [`System Tray`]: https://specifications.freedesktop.org/systemtray-spec/systemtray-spec-latest.html
+/// [`XEmbed`]: https://specifications.freedesktop.org/xembed-spec/xembed-spec-latest.html
+/// [`Cnx::run_headless()`]: ../widget/struct.Cnx.html#method.run_headless
+/// [`Cnx::render_once()`]: ../widget/struct.Cnx.html#method.render_once
+///
+/// # Examples
+///
+/// ```no_run
+/// # use rusty_bar::bar::{Alignment, Position};
+/// # use rusty_bar::text::{Attributes, Color, Font, Padding, VerticalAlignment};
+/// # use rusty_bar::tray::Tray;
+/// # use rusty_bar::widget::Cnx;
+/// # let attr = Attributes {
+/// #     font: Font::new("SourceCodePro 21"),
+/// #     fg_color: Color::white(),
+/// #     bg_color: None,
+/// #     padding: Padding::new(8.0, 8.0, 2.0, 2.0),
+/// #     valign: VerticalAlignment::Center,
+/// # };
+/// let mut cnx = Cnx::new(Position::Top);
+/// cnx.add_widget(Tray::new(attr)).align(Alignment::Right);
+/// ```
+pub struct Tray {
+    attr: Attributes,
+    position: Position,
+    container: Option<Container>,
+}
+
+impl Tray {
+    /// Creates a new Tray widget, for a bar at the top of the screen.
+    pub fn new(attr: Attributes) -> Tray {
+        Tray {
+            attr,
+            position: Position::Top,
+            container: None,
+        }
+    }
+
+    /// Sets the position of the bar that the tray is shown on, which needs to
+    /// match the position passed to [`Cnx::new()`] for the icons to fit.
+    ///
+    /// [`Cnx::new()`]: ../widget/struct.Cnx.html#method.new
+    pub fn with_position(self, position: Position) -> Tray {
+        Tray { position, ..self }
+    }
+}
+
+impl Widget for Tray {
+    fn into_stream(self: Box<Self>) -> Result<WidgetStream> {
+        // Without a bar to embed the container in (e.g. when running
+        // headless), there's nowhere to show the icons.
+        let container = self
+            .container
+            .ok_or_else(|| anyhow!("The tray can only be shown on a bar"))?;
+        let orientation = self.position.orientation();
+        let mut state =
+            TrayState::new(self.attr, orientation, container).context("Initialising Tray")?;
+
+        let events = XcbEventStream::new(state.container.conn.clone())?;
+        let initial: Result<Vec<Text>> = Ok(state.texts());
+        let stream = stream::once(initial).chain(events.filter_map(move |event| {
+            if state.process_event(&event) {
+                Some(Ok(state.texts()))
+            } else {
+                None
+            }
+        }));
+
+        Ok(Box::pin(stream))
+    }
+
+    fn embedded_window(&mut self) -> Result<Option<xcb::Window>> {
+        let container = Container::create()?;
+        let window = container.window;
+        self.container = Some(container);
+        Ok(Some(window))
+    }
+}
+
+// The window that the icons are embedded in, which is in turn embedded in the
+// bar. It has its own connection, as it needs to receive the events for the
+// icons.
+//
+// The window is destroyed when the connection is closed (along with its
+// colormap, if it has one), and any icons still in it are moved back to the
+// root window by the X server.
+struct Container {
+    conn: Rc<ewmh::Connection>,
+    screen_idx: usize,
+    window: xcb::Window,
+}
+
+impl Container {
+    fn create() -> Result<Container> {
+        let (conn, screen_idx) = connect()?;
+        let window = conn.generate_id();
+        {
+            let screen = conn
+                .get_setup()
+                .roots()
+                .nth(screen_idx)
+                .ok_or_else(|| anyhow!("Invalid screen"))?;
+            // Use the same visual as the bar, so that the bar shows through
+            // around the icons: with an ARGB visual the background is
+            // transparent, and otherwise the bar's background is copied.
+            let visual = choose_visual(&conn, screen_idx, &screen);
+            let background = if visual.depth == 32 {
+                (xcb::CW_BACK_PIXEL, 0)
+            } else {
+                (xcb::CW_BACK_PIXMAP, xcb::BACK_PIXMAP_PARENT_RELATIVE)
+            };
+            let colormap = visual.colormap.unwrap_or_else(|| screen.default_colormap());
+            let values = [
+                background,
+                (xcb::CW_BORDER_PIXEL, 0),
+                (xcb::CW_OVERRIDE_REDIRECT, 1),
+                // Property changes give us the server's timestamps, see
+                // `server_time()`.
+                (
+                    xcb::CW_EVENT_MASK,
+                    xcb::EVENT_MASK_STRUCTURE_NOTIFY
+                        | xcb::EVENT_MASK_SUBSTRUCTURE_NOTIFY
+                        | xcb::EVENT_MASK_PROPERTY_CHANGE,
+                ),
+                (xcb::CW_COLORMAP, colormap),
+            ];
+            xcb::create_window(
+                &conn,
+                visual.depth,
+                window,
+                screen.root(),
+                0,
+                0,
+                1,
+                1,
+                0,
+                xcb::WINDOW_CLASS_INPUT_OUTPUT as u16,
+                visual.visual.visual_id(),
+                &values,
+            );
+        }
+        conn.flush();
+
+        Ok(Container {
+            conn,
+            screen_idx,
+            window,
+        })
+    }
+}
+
+// Returns the time of the X server at which `property` of `window` was last
+// changed, by waiting for the `PropertyNotify` event of the change.
+//
+// This is called before the tray's event stream starts, so any other events
+// received in the meantime are dropped. They can only be about the container
+// itself, as no icons have docked yet.
+fn server_time(
+    conn: &xcb::Connection,
+    window: xcb::Window,
+    property: xcb::Atom,
+) -> Result<xcb::Timestamp> {
+    conn.flush();
+    loop {
+        let event = conn
+            .wait_for_event()
+            .ok_or_else(|| anyhow!("Lost the connection to the X server"))?;
+        if event.response_type() & !0x80 == xcb::PROPERTY_NOTIFY {
+            let event: &xcb::PropertyNotifyEvent = unsafe { xcb::cast_event(&event) };
+            if event.window() == window && event.atom() == property {
+                return Ok(event.time());
+            }
+        }
+    }
+}
+
+struct Atoms {
+    selection: xcb::Atom,
+    opcode: xcb::Atom,
+    orientation: xcb::Atom,
+    manager: xcb::Atom,
+    xembed: xcb::Atom,
+    xembed_info: xcb::Atom,
+}
+
+impl Atoms {
+    fn intern(conn: &xcb::Connection, screen_idx: usize) -> Result<Atoms> {
+        let intern = |name: &str| -> Result<xcb::Atom> {
+            let reply = xcb::intern_atom(conn, false, name).get_reply()?;
+            Ok(reply.atom())
+        };
+        Ok(Atoms {
+            selection: intern(&format!("_NET_SYSTEM_TRAY_S{screen_idx}"))?,
+            opcode: intern("_NET_SYSTEM_TRAY_OPCODE")?,
+            orientation: intern("_NET_SYSTEM_TRAY_ORIENTATION")?,
+            manager: intern("MANAGER")?,
+            xembed: intern("_XEMBED")?,
+            xembed_info: intern("_XEMBED_INFO")?,
+        })
+    }
+}
+
+struct TrayState {
+    attr: Attributes,
+    // The orientation of the bar, which the icons are laid out along.
+    orientation: Orientation,
+    container: Container,
+    atoms: Atoms,
+    root: xcb::Window,
+    icons: Vec<xcb::Window>,
+    // The docked icons that have asked not to be shown, through their
+    // `_XEMBED_INFO`. They take up no space.
+    hidden: HashSet<xcb::Window>,
+    // The size of the container across the bar (i.e. its height, on a
+    // horizontal bar), as set by the bar.
+    breadth: u16,
+    // Whether we are still the system tray manager.
+    active: bool,
+}
+
+impl TrayState {
+    // Becomes the system tray manager of the screen, and tells any running
+    // applications so that they dock their icons.
+    fn new(attr: Attributes, orientation: Orientation, container: Container) -> Result<TrayState> {
+        let conn = &container.conn;
+        let atoms = Atoms::intern(conn, container.screen_idx).context("Failed to intern atoms")?;
+        let root = conn
+            .get_setup()
+            .roots()
+            .nth(container.screen_idx)
+            .ok_or_else(|| anyhow!("Invalid screen"))?
+            .root();
+
+        // As defined by the System Tray protocol.
+        let tray_orientation: u32 = match orientation {
+            Orientation::Horizontal => 0,
+            Orientation::Vertical => 1,
+        };
+        xcb::change_property(
+            conn,
+            xcb::PROP_MODE_REPLACE as u8,
+            container.window,
+            atoms.orientation,
+            xcb::ATOM_CARDINAL,
+            32,
+            &[tray_orientation],
+        );
+
+        // The selection must be claimed (and the claim announced) with a
+        // real timestamp rather than `CURRENT_TIME`, as the ICCCM requires.
+        let time = server_time(conn, container.window, atoms.orientation)?;
+        xcb::set_selection_owner(conn, container.window, atoms.selection, time);
+        let owner = xcb::get_selection_owner(conn, atoms.selection)
+            .get_reply()?
+            .owner();
+        if owner != container.window {
+            return Err(anyhow!("Failed to become the system tray manager"));
+        }
+
+        let data = [time, atoms.selection, container.window, 0, 0];
+        let event = xcb::ClientMessageEvent::new(
+            32,
+            root,
+            atoms.manager,
+            xcb::ClientMessageData::from_data32(data),
+        );
+        xcb::send_event(conn, false, root, xcb::EVENT_MASK_STRUCTURE_NOTIFY, &event);
+        conn.flush();
+
+        Ok(TrayState {
+            attr,
+            orientation,
+            container,
+            atoms,
+            root,
+            icons: Vec::new(),
+            hidden: HashSet::new(),
+            breadth: 1,
+            active: true,
+        })
+    }
+
+    fn conn(&self) -> &ewmh::Connection {
+        &self.container.conn
+    }
+
+    // The size of each (square) icon, which fills the bar across, inside the
+    // widget's padding.
+    fn icon_size(&self) -> u16 {
+        let padding = &self.attr.padding;
+        let size = match self.orientation {
+            Orientation::Horizontal => f64::from(self.breadth) - padding.top - padding.bottom,
+            Orientation::Vertical => f64::from(self.breadth) - padding.left - padding.right,
+        };
+        size.max(1.0) as u16
+    }
+
+    // The docked icons that are shown, in the order they docked in.
+    fn visible_icons(&self) -> impl Iterator<Item = xcb::Window> + '_ {
+        self.icons
+            .iter()
+            .copied()
+            .filter(move |icon| !self.hidden.contains(icon))
+    }
+
+    // A single empty text, padded to be as long as the icons along the bar.
+    fn texts(&self) -> Vec<Text> {
+        let icons_length = f64::from(self.icon_size()) * self.visible_icons().count() as f64;
+        let padding = &self.attr.padding;
+        let mut attr = self.attr.clone();
+        attr.padding = match self.orientation {
+            Orientation::Horizontal => Padding::new(
+                padding.left + icons_length,
+                padding.right,
+                padding.top,
+                padding.bottom,
+            ),
+            Orientation::Vertical => Padding::new(
+                padding.left,
+                padding.right,
+                padding.top + icons_length,
+                padding.bottom,
+            ),
+        };
+        vec![Text {
+            attr,
+            text: String::new(),
+            stretch: false,
+            markup: false,
+        }]
+    }
+
+    // Handles an event on our connection, returning whether the widget's
+    // length may have changed.
+    fn process_event(&mut self, event: &xcb::GenericEvent) -> bool {
+        match event.response_type() & !0x80 {
+            xcb::CLIENT_MESSAGE => {
+                let event: &xcb::ClientMessageEvent = unsafe { xcb::cast_event(event) };
+                let data = event.data().data32();
+                if self.active
+                    && event.type_() == self.atoms.opcode
+                    && data[1] == SYSTEM_TRAY_REQUEST_DOCK
+                {
+                    return self.dock(data[2]);
+                }
+                false
+            }
+            xcb::DESTROY_NOTIFY => {
+                let event: &xcb::DestroyNotifyEvent = unsafe { xcb::cast_event(event) };
+                self.undock(event.window())
+            }
+            xcb::REPARENT_NOTIFY => {
+                // The icon has been moved out of the container.
+                let event: &xcb::ReparentNotifyEvent = unsafe { xcb::cast_event(event) };
+                if event.parent() != self.container.window {
+                    self.undock(event.window())
+                } else {
+                    false
+                }
+            }
+            xcb::CONFIGURE_NOTIFY => {
+                // The bar has resized the container, so resize the icons.
+                let event: &xcb::ConfigureNotifyEvent = unsafe { xcb::cast_event(event) };
+                let breadth = match self.orientation {
+                    Orientation::Horizontal => event.height(),
+                    Orientation::Vertical => event.width(),
+                };
+                if event.window() == self.container.window && breadth != self.breadth {
+                    self.breadth = breadth;
+                    self.layout_icons();
+                    true
+                } else {
+                    false
+                }
+            }
+            xcb::PROPERTY_NOTIFY => {
+                // An icon may have asked to be shown or hidden.
+                let event: &xcb::PropertyNotifyEvent = unsafe { xcb::cast_event(event) };
+                if event.atom() == self.atoms.xembed_info && self.icons.contains(&event.window()) {
+                    self.update_mapping(event.window())
+                } else {
+                    false
+                }
+            }
+            xcb::SELECTION_CLEAR => {
+                // Another system tray has taken over. Hand the icons back to
+                // the root window, from where they'll dock with it.
+                let event: &xcb::SelectionClearEvent = unsafe { xcb::cast_event(event) };
+                if event.selection() != self.atoms.selection {
+                    return false;
+                }
+                self.active = false;
+                self.hidden.clear();
+                for icon in self.icons.drain(..) {
+                    xcb::unmap_window(&self.container.conn, icon);
+                    xcb::reparent_window(&self.container.conn, icon, self.root, 0, 0);
+                }
+                self.conn().flush();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn dock(&mut self, icon: xcb::Window) -> bool {
+        if self.icons.contains(&icon) {
+            return false;
+        }
+
+        let conn = &self.container.conn;
+        // Have the X server move the icon back to the root window if we exit.
+        xcb::change_save_set(conn, xcb::SET_MODE_INSERT as u8, icon);
+        // Hear about changes to its `_XEMBED_INFO`.
+        xcb::change_window_attributes(
+            conn,
+            icon,
+            &[(xcb::CW_EVENT_MASK, xcb::EVENT_MASK_PROPERTY_CHANGE)],
+        );
+        xcb::reparent_window(conn, icon, self.container.window, 0, 0);
+
+        let data = [
+            xcb::CURRENT_TIME,
+            XEMBED_EMBEDDED_NOTIFY,
+            0,
+            self.container.window,
+            XEMBED_VERSION,
+        ];
+        let event = xcb::ClientMessageEvent::new(
+            32,
+            icon,
+            self.atoms.xembed,
+            xcb::ClientMessageData::from_data32(data),
+        );
+        xcb::send_event(conn, false, icon, xcb::EVENT_MASK_NO_EVENT, &event);
+
+        if self.wants_mapping(icon) {
+            xcb::map_window(conn, icon);
+        } else {
+            self.hidden.insert(icon);
+        }
+
+        self.icons.push(icon);
+        self.layout_icons();
+        true
+    }
+
+    fn undock(&mut self, icon: xcb::Window) -> bool {
+        let len = self.icons.len();
+        self.icons.retain(|&docked| docked != icon);
+        if self.icons.len() == len {
+            return false;
+        }
+        self.hidden.remove(&icon);
+        self.layout_icons();
+        true
+    }
+
+    // Shows or hides the icon as its `_XEMBED_INFO` says, returning whether
+    // that changed.
+    fn update_mapping(&mut self, icon: xcb::Window) -> bool {
+        let mapped = self.wants_mapping(icon);
+        if mapped != self.hidden.contains(&icon) {
+            return false;
+        }
+        if mapped {
+            self.hidden.remove(&icon);
+            xcb::map_window(self.conn(), icon);
+        } else {
+            self.hidden.insert(icon);
+            xcb::unmap_window(self.conn(), icon);
+        }
+        self.layout_icons();
+        true
+    }
+
+    // Whether the icon wants to be shown, according to its `_XEMBED_INFO`.
+    // Icons without the property are always shown.
+    fn wants_mapping(&self, icon: xcb::Window) -> bool {
+        let reply = xcb::get_property(
+            self.conn(),
+            false,
+            icon,
+            self.atoms.xembed_info,
+            xcb::ATOM_ANY,
+            0,
+            2,
+        )
+        .get_reply();
+        match reply {
+            Ok(reply) if reply.format() == 32 => match reply.value::<u32>() {
+                [_version, flags] => flags & XEMBED_MAPPED != 0,
+                _ => true,
+            },
+            _ => true,
+        }
+    }
+
+    // Places the icons next to each other along the bar (side by side, or on
+    // top of each other on a vertical bar), inside the widget's padding.
+    fn layout_icons(&self) {
+        let size = self.icon_size();
+        let padding = &self.attr.padding;
+        for (i, icon) in self.visible_icons().enumerate() {
+            let offset = f64::from(size) * i as f64;
+            let (x, y) = match self.orientation {
+                Orientation::Horizontal => (padding.left + offset, padding.top),
+                Orientation::Vertical => (padding.left, padding.top + offset),
+            };
+            let values = [
+                (xcb::CONFIG_WINDOW_X as u16, x as u32),
+                (xcb::CONFIG_WINDOW_Y as u16, y as u32),
+                (xcb::CONFIG_WINDOW_WIDTH as u16, u32::from(size)),
+                (xcb::CONFIG_WINDOW_HEIGHT as u16, u32::from(size)),
+            ];
+            xcb::configure_window(self.conn(), icon, &values);
+        }
+        self.conn().flush();
+    }
+}
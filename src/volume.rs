@@ -1,6 +1,7 @@
 use alsa::mixer::{SelemChannelId, SelemId};
 use alsa::{self, Mixer, PollDescriptors};
 use anyhow::{anyhow, Context, Result};
+use crate::mouse::{MouseButton, MouseEvent, MouseEventKind, MouseHandler};
 use crate::text::{Attributes, Text};
 use crate::widget::{Widget, WidgetStream};
 use std::os::unix::io::AsRawFd;
@@ -10,11 +11,16 @@ use std::task::Poll;
 use tokio::io::unix::AsyncFd;
 use tokio_stream::{Stream, StreamExt};
 
+const MIXER_NAME: &str = "default";
+
 /// Shows the current volume of the default ALSA output.
 ///
 /// This widget shows the current volume of the default ALSA output, or '`M`' if
 /// the output is muted.
 ///
+/// Scrolling over the widget raises or lowers the volume by 5%, and clicking
+/// on it toggles mute.
+///
 /// The widget uses `alsa-lib` to receive events when the volume changes,
 /// avoiding expensive polling. If you do not have `alsa-lib` installed, you
 /// can disable the `volume-widget` feature on the `cnx` crate to avoid
@@ -56,6 +62,7 @@ impl Volume {
     ///     fg_color: Color::white(),
     ///     bg_color: None,
     ///     padding: Padding::new(8.0, 8.0, 0.0, 0.0),
+    ///     valign: VerticalAlignment::Center,
     /// };
     ///
     /// let mut cnx = Cnx::new(Position::Top);
@@ -69,11 +76,65 @@ impl Volume {
     }
 }
 
+// Changes the volume with `mixer`, which is opened on the first event and then
+// kept for the rest.
+fn on_mouse_event(mixer: &mut Option<Mixer>, event: MouseEvent) -> Result<()> {
+    if event.kind != MouseEventKind::Press {
+        return Ok(());
+    }
+
+    let channel = SelemChannelId::FrontLeft;
+    let mixer = match mixer {
+        Some(mixer) => {
+            // Catch up with any changes made elsewhere since the last event,
+            // which the mixer doesn't see otherwise.
+            let _poll_result = alsa::poll::poll_all(&[&*mixer], 0);
+            mixer.handle_events()?;
+            mixer
+        }
+        None => mixer.insert(
+            Mixer::new(MIXER_NAME, false)
+                .with_context(|| format!("Failed to open ALSA mixer: {MIXER_NAME}"))?,
+        ),
+    };
+    let master = mixer
+        .find_selem(&SelemId::new("Master", 0))
+        .ok_or_else(|| anyhow!("Couldn't open Master channel"))?;
+
+    let (min, max) = master.get_playback_volume_range();
+    let step = (max - min) / 20;
+    match event.button {
+        MouseButton::ScrollUp | MouseButton::ScrollDown => {
+            let volume = master.get_playback_volume(channel)?;
+            let volume = if event.button == MouseButton::ScrollUp {
+                volume + step
+            } else {
+                volume - step
+            };
+            master.set_playback_volume_all(volume.clamp(min, max))?;
+        }
+        MouseButton::Left => {
+            let unmuted = master.get_playback_switch(channel)?;
+            master.set_playback_switch_all(if unmuted == 0 { 1 } else { 0 })?;
+        }
+        _ => {}
+    }
+
+    // The change will be picked up by the AlsaEventStream, which will update
+    // the widget's text.
+    Ok(())
+}
+
 // https://github.com/mjkillough/cnx/blob/92c24238be541c75d88181208862505739be33fd/src/widgets/volume.rs
 
 impl Widget for Volume {
+    fn mouse_handler(&mut self) -> Option<MouseHandler> {
+        let mut mixer = None;
+        Some(Box::new(move |event| on_mouse_event(&mut mixer, event)))
+    }
+
     fn into_stream(self: Box<Self>) -> Result<WidgetStream> {
-        let mixer_name = "default";
+        let mixer_name = MIXER_NAME;
         // We don't attempt to use the same mixer to listen for events and to
         // recompute the mixer state (in the callback below) as the Mixer seems
         // to cache the state from when it was created. It's relatively cheap
@@ -0,0 +1,115 @@
+use anyhow::{anyhow, Context as _AnyhowContext, Result};
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify, WatchDescriptor};
+use std::ffi::OsString;
+use std::fs;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::io::RawFd;
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::unix::AsyncFd;
+use tokio_stream::Stream;
+
+// A wrapper around `Inotify` that closes the file descriptor when dropped.
+struct InotifyEvented(Inotify);
+
+impl AsRawFd for InotifyEvented {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl Drop for InotifyEvented {
+    fn drop(&mut self) {
+        let _ = nix::unistd::close(self.0.as_raw_fd());
+    }
+}
+
+// A `Stream` that yields each time the file at the given path is written to
+// or replaced.
+//
+// The parent directory is watched rather than the file itself, as most
+// editors save by writing a new file and moving it over the old one. That
+// also means the file doesn't need to exist yet, only its directory.
+pub struct FileWatchStream {
+    poll: AsyncFd<InotifyEvented>,
+    // Each watched directory, along with the name of the file in it.
+    files: Vec<(WatchDescriptor, OsString)>,
+}
+
+impl FileWatchStream {
+    pub fn new(path: &Path) -> Result<FileWatchStream> {
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)
+            .context("Failed to initialise inotify")?;
+        let evented = InotifyEvented(inotify);
+        let mut files = vec![watch(&evented.0, path)?];
+
+        // If the file is a symlink (e.g. into a repository of dotfiles), the
+        // file it points to is edited rather than the link, so watch that
+        // too.
+        let is_symlink = fs::symlink_metadata(path)
+            .map(|metadata| metadata.file_type().is_symlink())
+            .unwrap_or(false);
+        if is_symlink {
+            let target = fs::canonicalize(path)
+                .with_context(|| format!("Failed to resolve {}", path.display()))?;
+            files.push(watch(&evented.0, &target)?);
+        }
+
+        Ok(FileWatchStream {
+            poll: AsyncFd::with_interest(evented, tokio::io::Interest::READABLE)?,
+            files,
+        })
+    }
+}
+
+// Watches the directory of the file at `path`, returning the watch along with
+// the name of the file.
+fn watch(inotify: &Inotify, path: &Path) -> Result<(WatchDescriptor, OsString)> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("Not a file: {}", path.display()))?
+        .to_owned();
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let watch = inotify
+        .add_watch(
+            dir,
+            AddWatchFlags::IN_CLOSE_WRITE | AddWatchFlags::IN_MOVED_TO,
+        )
+        .with_context(|| format!("Failed to watch {}", dir.display()))?;
+    Ok((watch, file_name))
+}
+
+impl Stream for FileWatchStream {
+    // We don't bother yielding the events, just that the file changed.
+    type Item = ();
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        loop {
+            let mut ready = match self.poll.poll_read_ready(cx) {
+                Poll::Ready(Ok(r)) => r,
+                Poll::Ready(Err(_)) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            };
+
+            match self.poll.get_ref().0.read_events() {
+                Ok(events) => {
+                    let changed = events.iter().any(|event| {
+                        self.files
+                            .iter()
+                            .any(|(wd, name)| event.wd == *wd && event.name.as_ref() == Some(name))
+                    });
+                    if changed {
+                        return Poll::Ready(Some(()));
+                    }
+                }
+                // The inotify fd is non-blocking, so this means we've read
+                // all pending events. Wait to be woken up again.
+                Err(_) => ready.clear_ready(),
+            }
+        }
+    }
+}
@@ -1,15 +1,19 @@
 use anyhow::Result;
 use async_stream::try_stream;
-use cnx::text::{Attributes, Text};
-use cnx::widgets::{Widget, WidgetStream};
 use std::time::Duration;
+use tokio_stream::StreamExt;
 use weathernoaa::weather::*;
 
+use crate::schedule;
+use crate::text::{Attributes, Text};
+use crate::widget::{Widget, WidgetStream};
+
 /// Represents Weather widget used to show current weather information.
 pub struct Weather {
     attr: Attributes,
     station_code: String,
     render: Option<Box<dyn Fn(WeatherInfo) -> String>>,
+    update_interval: Duration,
 }
 
 impl Weather {
@@ -35,12 +39,10 @@ impl Weather {
     /// # Examples
     ///
     /// ```
-    /// # #[macro_use]
-    /// # extern crate cnx;
-    /// #
-    /// # use cnx::*;
-    /// # use cnx::text::*;
-    /// # use cnx_contrib::widgets::weather::*;
+    /// # use rusty_bar::bar::Position;
+    /// # use rusty_bar::text::*;
+    /// # use rusty_bar::weather::*;
+    /// # use rusty_bar::widget::Cnx;
     /// # use anyhow::Result;
     /// #
     /// # fn run() -> Result<()> {
@@ -49,6 +51,7 @@ impl Weather {
     ///     fg_color: Color::white(),
     ///     bg_color: None,
     ///     padding: Padding::new(8.0, 8.0, 0.0, 0.0),
+    ///     valign: VerticalAlignment::Center,
     /// };
     ///
     /// let mut cnx = Cnx::new(Position::Top);
@@ -66,14 +69,24 @@ impl Weather {
             attr,
             station_code,
             render,
+            update_interval: Duration::from_secs(30 * 60),
+        }
+    }
+
+    /// Sets how often the weather is updated. The default is half an hour.
+    pub fn with_interval(self, update_interval: Duration) -> Self {
+        Self {
+            update_interval,
+            ..self
         }
     }
 }
 
 impl Widget for Weather {
     fn into_stream(self: Box<Self>) -> Result<WidgetStream> {
+        let mut ticks = schedule::ticks(self.update_interval);
         let stream = try_stream! {
-            loop {
+            while let Some(()) = ticks.next().await {
                 let weather = get_weather(self.station_code.clone()).await?;
                 let text = self.render.as_ref().map_or(format!("Temp: {}°C", weather.temperature.celsius), |x| (x)(weather));
                 let texts = vec![Text {
@@ -83,10 +96,6 @@ impl Widget for Weather {
                     markup: true,
                 }];
                 yield texts;
-
-                let thirty_minutes = 30 * 60;
-                let sleep_for = Duration::from_secs(thirty_minutes);
-                tokio::time::sleep(sleep_for).await;
             }
         };
         Ok(Box::pin(stream))
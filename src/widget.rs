@@ -1,7 +1,10 @@
-use anyhow::Result;
+use anyhow::{anyhow, Error, Result};
 use futures::stream::Stream;
+use std::collections::HashMap;
 use std::pin::Pin;
 
+use crate::mouse::{MouseEvent, MouseHandler};
+
 /// The stream of `Vec<Text>` returned by each widget.
 ///
 /// This simple type alias makes referring to this stream a little easier. For
@@ -22,20 +25,152 @@ pub type WidgetStream = Pin<Box<dyn Stream<Item = Result<Vec<Text>>>>>;
 /// See the [`WidgetStream`] type alias for the exact type of stream that
 /// should be returned.
 ///
+/// Widgets that react to clicks or scrolling can also provide a
+/// [`MouseHandler`] by implementing [`Widget::mouse_handler()`], and widgets
+/// that show a window of their own (like the system tray) can have it embedded
+/// in the bar by implementing [`Widget::embedded_window()`].
+///
 pub trait Widget {
     fn into_stream(self: Box<Self>) -> Result<WidgetStream>;
+
+    /// Returns the handler for mouse events on this widget's texts.
+    ///
+    /// This is called once, before [`Widget::into_stream()`]. Widgets that
+    /// don't react to the mouse can rely on the default implementation, which
+    /// returns `None`.
+    fn mouse_handler(&mut self) -> Option<MouseHandler> {
+        None
+    }
+
+    /// Returns a window to embed in the bar, covering this widget's first
+    /// text.
+    ///
+    /// This is called once, before [`Widget::into_stream()`], and only when
+    /// the widget is shown on an X server. The window is moved and resized
+    /// whenever the widget's text is. Most widgets can rely on the default
+    /// implementation, which returns `None`.
+    fn embedded_window(&mut self) -> Result<Option<xcb::Window>> {
+        Ok(None)
+    }
+
+    /// Returns a short name for the widget, used as part of the target of
+    /// its log messages.
+    ///
+    /// The default implementation returns the name of the module the widget
+    /// is defined in, e.g. `clock` for [`Clock`].
+    ///
+    /// [`Clock`]: ../clock/struct.Clock.html
+    fn name(&self) -> &'static str {
+        let path = std::any::type_name::<Self>();
+        let path = path.split('<').next().unwrap_or(path);
+        path.rsplit("::").nth(1).unwrap_or(path)
+    }
 }
 
 
 
+use futures::stream::FuturesUnordered;
+use futures::Future;
+use log::{error, info, warn};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
 use tokio::runtime::Runtime;
 use tokio::task;
+use tokio::time::{self, Instant};
 use tokio_stream::{StreamExt, StreamMap};
+use xcb_util::ewmh;
+
+use crate::backend::ImageBackend;
+use crate::bar::{Alignment, Bar, Offset, Position, Rect};
+use crate::bars::{BarOptions, Bars, Placement};
+use crate::config::Config;
+use crate::ipc::{Incoming, Request, Response, Server, WidgetId, WidgetInfo};
+use crate::ipc_text;
+use crate::logging::{widget_target, RateLimiter};
+use crate::output::{Output, OutputFormat, Printer};
+use crate::randr::Monitors;
+use crate::signal::{self, Signals};
+use crate::supervisor::{catch_panic, supervise, SupervisedStream, WidgetEvent};
+use crate::watch::FileWatchStream;
+use crate::xcb::{connect, connection_lost, XcbEventStream};
+use crate::text::{Attributes, Text};
+
+// How long `Cnx::render_once()` waits for the widgets' first content.
+const RENDER_ONCE_TIMEOUT: Duration = Duration::from_secs(5);
 
-use crate::bar::{Bar,Offset,Position};
-use crate::xcb::XcbEventStream;
-use crate::text::Text;
+// The width of the image drawn by `Cnx::render_once()`, if none is set with
+// `Cnx::with_width()`.
+const DEFAULT_IMAGE_WIDTH: u16 = 1920;
+
+// The height of the screen that vertical bars drawn by `Cnx::render_once()`
+// span.
+const DEFAULT_IMAGE_HEIGHT: u16 = 1080;
+
+// How long to wait between attempts to reconnect to the X server, see
+// `Cnx::with_reconnect()`.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// The name of the bar (and of its windows and socket), if none is set with
+/// [`Cnx::with_name()`].
+///
+/// [`Cnx::with_name()`]: struct.Cnx.html#method.with_name
+pub const DEFAULT_NAME: &str = "rusty-bar";
 
+// How long to wait before restarting a failed widget for the first time. The
+// delay doubles with each consecutive failure, up to `MAX_RESTART_DELAY`.
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(300);
+
+// Creates a new instance of a widget, see `Cnx::add_widget_fn()`.
+type WidgetFactory = Box<dyn FnMut() -> Result<Box<dyn Widget>>>;
+
+/// How a widget that has failed is shown on the bar.
+///
+/// Passed to [`Cnx::with_error_display()`].
+///
+/// [`Cnx::with_error_display()`]: struct.Cnx.html#method.with_error_display
+#[derive(Clone, Debug)]
+pub struct ErrorDisplay {
+    /// The attributes of the text shown in place of the widget.
+    pub attr: Attributes,
+    /// The text shown in place of the widget, in which `{error}` is replaced
+    /// by the error message.
+    pub format: String,
+}
+
+impl ErrorDisplay {
+    // Returns the text to show for `error`.
+    pub(crate) fn text(&self, error: &str) -> Text {
+        Text {
+            attr: self.attr.clone(),
+            text: self.format.replace("{error}", error),
+            stretch: false,
+            markup: false,
+        }
+    }
+}
+
+/// When a widget added with [`Cnx::add_widget_fn()`] is restarted.
+///
+/// Passed to [`WidgetEntry::restart_policy()`]. Whatever the policy, a widget
+/// that panics is stopped rather than taking the bar down with it.
+///
+/// [`Cnx::add_widget_fn()`]: struct.Cnx.html#method.add_widget_fn
+/// [`WidgetEntry::restart_policy()`]: struct.WidgetEntry.html#method.restart_policy
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RestartPolicy {
+    /// Never restart the widget. A widget that yields an error keeps running,
+    /// in case it recovers by itself.
+    Never,
+    /// Restart the widget when it yields an error, fails to start or panics.
+    OnFailure,
+    /// Also restart the widget when its stream ends, e.g. because the
+    /// process it reads from has exited.
+    #[default]
+    Always,
+}
 
 /// The main object, used to instantiate an instance of Cnx.
 ///
@@ -49,13 +184,78 @@ pub struct Cnx {
     /// The position of the Cnx bar
     position: Position,
     /// The list of widgets attached to the Cnx bar
-    widgets: Vec<Box<dyn Widget>>,
+    widgets: Vec<WidgetEntry>,
     /// The (x,y) offset of the bar
     /// It can be used in order to run multiple bars in a multi-monitor setup
     offset: Offset,
     /// The (optional) width of the bar
     /// It can be used in order to run multiple bars in a multi-monitor setup
     width: Option<u16>,
+    /// The (optional) fixed height of the bar
+    height: Option<u16>,
+    /// The (optional) monitors to show a bar on
+    /// If set, the width and offset are ignored
+    monitors: Option<Monitors>,
+    /// The name of the bar's windows
+    name: String,
+    /// Whether to reconnect to the X server if the connection is lost
+    reconnect: bool,
+    /// How to show widgets that have failed, if at all
+    error_display: Option<ErrorDisplay>,
+    /// The (optional) configuration file to reload the bar from when it
+    /// changes
+    config_path: Option<PathBuf>,
+}
+
+/// A widget attached to a [`Cnx`] instance.
+///
+/// Returned by [`Cnx::add_widget()`] so that the way the widget is placed on
+/// the bar can be adjusted.
+///
+/// [`Cnx::add_widget()`]: struct.Cnx.html#method.add_widget
+pub struct WidgetEntry {
+    widget: Box<dyn Widget>,
+    // Creates a replacement for the widget if it fails, if it was added with
+    // `Cnx::add_widget_fn()`.
+    factory: Option<WidgetFactory>,
+    restart_policy: RestartPolicy,
+    alignment: Alignment,
+    refresh_signal: Option<u32>,
+}
+
+impl WidgetEntry {
+    /// Places the widget in the given zone of the bar.
+    ///
+    /// See [`Alignment`] for how each zone is laid out.
+    pub fn align(&mut self, alignment: Alignment) -> &mut Self {
+        self.alignment = alignment;
+        self
+    }
+
+    /// Sets when the widget is restarted, if it was added with
+    /// [`Cnx::add_widget_fn()`]. The default is [`RestartPolicy::Always`].
+    ///
+    /// Widgets added with [`Cnx::add_widget()`] are never restarted.
+    ///
+    /// [`Cnx::add_widget_fn()`]: struct.Cnx.html#method.add_widget_fn
+    /// [`Cnx::add_widget()`]: struct.Cnx.html#method.add_widget
+    /// [`RestartPolicy::Always`]: enum.RestartPolicy.html#variant.Always
+    pub fn restart_policy(&mut self, restart_policy: RestartPolicy) -> &mut Self {
+        self.restart_policy = restart_policy;
+        self
+    }
+
+    /// Refreshes the widget whenever the bar receives the real-time signal
+    /// `SIGRTMIN+signal`, e.g. `pkill -RTMIN+3 rusty-bar` for a `signal` of 3.
+    ///
+    /// The widget is refreshed by replacing it with a new instance, so this
+    /// only works for widgets added with [`Cnx::add_widget_fn()`].
+    ///
+    /// [`Cnx::add_widget_fn()`]: struct.Cnx.html#method.add_widget_fn
+    pub fn refresh_signal(&mut self, signal: u32) -> &mut Self {
+        self.refresh_signal = Some(signal);
+        self
+    }
 }
 
 impl Cnx {
@@ -72,6 +272,12 @@ impl Cnx {
             widgets,
             offset: Offset::default(),
             width: None,
+            height: None,
+            monitors: None,
+            name: DEFAULT_NAME.to_owned(),
+            reconnect: false,
+            error_display: None,
+            config_path: None,
         }
     }
 
@@ -86,6 +292,20 @@ impl Cnx {
         Self { width, ..self }
     }
 
+    /// Returns a new instance of `Cnx` with the specified height.
+    ///
+    /// By default the bar is as tall as its tallest text. With a fixed height,
+    /// every text is placed within the height of the bar according to its
+    /// [`VerticalAlignment`], and backgrounds fill the full height. Texts
+    /// which are taller than the bar are cut off.
+    ///
+    /// For a bar on the left or right of the screen, this is its width.
+    ///
+    /// [`VerticalAlignment`]: ../text/enum.VerticalAlignment.html
+    pub fn with_height(self, height: Option<u16>) -> Self {
+        Self { height, ..self }
+    }
+
     /// Returns a new instance of `Cnx` with the specified offset.
     ///
     /// This allows to specify the x and y offset of the `Cnx` bar,
@@ -100,23 +320,191 @@ impl Cnx {
         }
     }
 
+    /// Returns a new instance of `Cnx` whose bars have the given name.
+    ///
+    /// The name is used for the `WM_NAME` and the instance part of the
+    /// `WM_CLASS` of each bar's window, so that window manager and compositor
+    /// rules can match it. With [`with_monitors()`], the name of the monitor
+    /// is appended to the instance name, e.g. `rusty-bar-eDP-1`. The default
+    /// name is `rusty-bar`, and the class is always `rusty-bar`.
+    ///
+    /// [`with_monitors()`]: #method.with_monitors
+    pub fn with_name(self, name: String) -> Self {
+        Self { name, ..self }
+    }
+
+    /// Returns a new instance of `Cnx` which reconnects to the X server if
+    /// the connection to it is lost.
+    ///
+    /// By default, [`run()`] returns a [`ConnectionLost`] error instead. When
+    /// reconnecting, the bars are recreated once the X server is back, while
+    /// the widgets keep running. Widgets with their own connection to the X
+    /// server (such as the window title) stop updating.
+    ///
+    /// [`run()`]: #method.run
+    /// [`ConnectionLost`]: ../xcb/struct.ConnectionLost.html
+    pub fn with_reconnect(self, reconnect: bool) -> Self {
+        Self { reconnect, ..self }
+    }
+
+    /// Returns a new instance of `Cnx` which shows failed widgets as set by
+    /// `error_display`.
+    ///
+    /// A widget has failed when its stream yields an error, or when it can't
+    /// be started. With `None` (the default), the widget's previous content
+    /// is left on the bar. Either way, the error is logged, and the widget
+    /// goes back to normal once it yields content again.
+    pub fn with_error_display(self, error_display: Option<ErrorDisplay>) -> Self {
+        Self {
+            error_display,
+            ..self
+        }
+    }
+
+    /// Returns a new instance of `Cnx` which shows a bar on each monitor.
+    ///
+    /// The monitors are discovered using the RandR extension. A bar is shown
+    /// on each monitor selected by [`Monitors`], spanning the full width of
+    /// that monitor. Bars are created, moved and destroyed as monitors are
+    /// connected, rearranged and disconnected.
+    ///
+    /// Every bar shows the same widgets. The width and offset set by
+    /// [`with_width()`] and [`with_offset()`] are ignored.
+    ///
+    /// [`Monitors`]: ../randr/enum.Monitors.html
+    /// [`with_width()`]: #method.with_width
+    /// [`with_offset()`]: #method.with_offset
+    pub fn with_monitors(self, monitors: Monitors) -> Self {
+        Self {
+            monitors: Some(monitors),
+            ..self
+        }
+    }
+
+    /// Returns a new instance of `Cnx` which reloads itself from the given
+    /// configuration file whenever it changes.
+    ///
+    /// The file is watched using inotify. When it changes, it is loaded with
+    /// [`Config::load()`] and the bar's position, placement and widgets are
+    /// replaced by the ones it describes, keeping the bar's window open. If
+    /// the file can't be loaded, the error is logged and the current widgets
+    /// are kept.
+    ///
+    /// This `Cnx` instance is usually the one built from that same file.
+    ///
+    /// [`Config::load()`]: ../config/struct.Config.html#method.load
+    pub fn with_config_path(self, path: PathBuf) -> Self {
+        Self {
+            config_path: Some(path),
+            ..self
+        }
+    }
+
     /// Adds a widget to the `Cnx` instance.
     ///
     /// Takes ownership of the [`Widget`] and adds it to the Cnx instance to
-    /// the right of any existing widgets.
+    /// the right of any existing widgets in the same zone. Widgets are placed
+    /// in the left zone unless the returned [`WidgetEntry`] is used to choose
+    /// another one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rusty_bar::bar::{Alignment, Position};
+    /// # use rusty_bar::clock::Clock;
+    /// # use rusty_bar::text::{Attributes, Color, Font, Padding, VerticalAlignment};
+    /// # use rusty_bar::widget::Cnx;
+    /// let attr = Attributes {
+    ///     font: Font::new("SourceCodePro 21"),
+    ///     fg_color: Color::white(),
+    ///     bg_color: None,
+    ///     padding: Padding::new(8.0, 8.0, 0.0, 0.0),
+    ///     valign: VerticalAlignment::Center,
+    /// };
+    ///
+    /// let mut cnx = Cnx::new(Position::Top);
+    /// cnx.add_widget(Clock::new(attr, None)).align(Alignment::Center);
+    /// ```
     ///
     /// [`Widget`]: widgets/trait.Widget.html
-    pub fn add_widget<W>(&mut self, widget: W)
+    pub fn add_widget<W>(&mut self, widget: W) -> &mut WidgetEntry
     where
         W: Widget + 'static,
     {
-        self.widgets.push(Box::new(widget));
+        self.widgets.push(WidgetEntry {
+            widget: Box::new(widget),
+            factory: None,
+            restart_policy: RestartPolicy::Never,
+            alignment: Alignment::default(),
+            refresh_signal: None,
+        });
+        self.widgets.last_mut().unwrap()
+    }
+
+    /// Adds a widget to the `Cnx` instance, which is restarted if it fails or
+    /// stops.
+    ///
+    /// Like [`add_widget()`], but takes a function which creates the widget.
+    /// It is called once straight away, returning any error, and again to
+    /// replace the widget whenever it needs restarting, as set by
+    /// [`WidgetEntry::restart_policy()`]. By default that's whenever its
+    /// stream yields an error or ends, or it can't be started or panics. The
+    /// first restart happens after a second, and the delay doubles each time
+    /// the widget is restarted without yielding any content, up to five
+    /// minutes.
+    ///
+    /// Widgets added with [`add_widget()`] can't be restarted, so they keep
+    /// running after yielding an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rusty_bar::bar::Position;
+    /// # use rusty_bar::battery::Battery;
+    /// # use rusty_bar::text::{Attributes, Color, Font, Padding, VerticalAlignment};
+    /// # use rusty_bar::widget::Cnx;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let attr = Attributes {
+    ///     font: Font::new("SourceCodePro 21"),
+    ///     fg_color: Color::white(),
+    ///     bg_color: None,
+    ///     padding: Padding::new(8.0, 8.0, 0.0, 0.0),
+    ///     valign: VerticalAlignment::Center,
+    /// };
+    ///
+    /// let mut cnx = Cnx::new(Position::Top);
+    /// cnx.add_widget_fn(move || {
+    ///     Ok(Box::new(Battery::new(attr.clone(), Color::red(), None, None)))
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`add_widget()`]: #method.add_widget
+    /// [`WidgetEntry::restart_policy()`]: struct.WidgetEntry.html#method.restart_policy
+    pub fn add_widget_fn<F>(&mut self, mut factory: F) -> Result<&mut WidgetEntry>
+    where
+        F: FnMut() -> Result<Box<dyn Widget>> + 'static,
+    {
+        self.widgets.push(WidgetEntry {
+            widget: factory()?,
+            factory: Some(Box::new(factory)),
+            restart_policy: RestartPolicy::default(),
+            alignment: Alignment::default(),
+            refresh_signal: None,
+        });
+        Ok(self.widgets.last_mut().unwrap())
     }
 
     /// Runs the Cnx instance.
     ///
     /// This method takes ownership of the Cnx instance and runs it until either
-    /// the process is terminated, or an internal error is returned.
+    /// the process is terminated, or an internal error is returned. If the
+    /// connection to the X server is lost (and [`with_reconnect()`] isn't
+    /// set), the error is a [`ConnectionLost`].
+    ///
+    /// [`with_reconnect()`]: #method.with_reconnect
+    /// [`ConnectionLost`]: ../xcb/struct.ConnectionLost.html
     pub fn run(self) -> Result<()> {
         // Use a single-threaded event loop. We aren't interested in
         // performance too much, so don't mind if we block the loop
@@ -128,43 +516,748 @@ impl Cnx {
         Ok(())
     }
 
-    async fn run_inner(self) -> Result<()> {
-        let mut bar = Bar::new(self.position, self.width, self.offset)?;
+    /// Draws the bar once into a PNG image at `path`, without connecting to an
+    /// X server.
+    ///
+    /// Each widget is started and the first content it yields is drawn.
+    /// Widgets that fail to start or that yield nothing within 5 seconds are
+    /// left empty. The image is as wide as set with [`with_width()`], or 1920
+    /// pixels otherwise, and as tall as the bar. Vertical bars are as wide as
+    /// their content and 1080 pixels tall.
+    ///
+    /// [`with_width()`]: #method.with_width
+    pub fn render_once(self, path: &Path) -> Result<()> {
+        let rt = Runtime::new()?;
+        let local = task::LocalSet::new();
+        local.block_on(&rt, self.render_once_inner(path))?;
+        Ok(())
+    }
 
-        let mut widgets = StreamMap::with_capacity(self.widgets.len());
-        for widget in self.widgets {
-            let idx = bar.add_content(Vec::new())?;
-            widgets.insert(idx, widget.into_stream()?);
+    /// Runs the Cnx instance without showing a bar, writing the content of
+    /// the widgets to stdout in the given format instead.
+    ///
+    /// This doesn't need an X server, so the widgets can be used as the
+    /// status command of another bar, e.g. of swaybar with
+    /// [`OutputFormat::I3bar`]. A line is written whenever the content
+    /// changes. Otherwise the widgets run as they do with [`run()`]: they are
+    /// restarted when they fail, and can be refreshed with `rusty-bar msg`.
+    /// Widgets that need an X server, like the window title, fail to start,
+    /// as does [`Tray`], which has no bar to embed its icons in. No mouse
+    /// events are passed on.
+    ///
+    /// [`Tray`]: ../tray/struct.Tray.html
+    ///
+    /// [`OutputFormat::I3bar`]: ../output/enum.OutputFormat.html#variant.I3bar
+    /// [`run()`]: #method.run
+    pub fn run_headless(self, format: OutputFormat) -> Result<()> {
+        let rt = Runtime::new()?;
+        let local = task::LocalSet::new();
+        local.block_on(&rt, self.run_headless_inner(format))?;
+        Ok(())
+    }
+
+    async fn render_once_inner(self, path: &Path) -> Result<()> {
+        let width = self.width.unwrap_or(DEFAULT_IMAGE_WIDTH);
+        let (options, entries) = self.into_parts();
+        let area = Rect {
+            width,
+            height: DEFAULT_IMAGE_HEIGHT,
+            ..Rect::default()
+        };
+        let mut bar = Bar::with_backend(ImageBackend::new(width)?, options.position, area)?;
+        bar.set_fixed_breadth(options.height);
+
+        let mut streams = StreamMap::with_capacity(entries.len());
+        let mut targets = Vec::with_capacity(entries.len());
+        for (idx, entry) in entries.into_iter().enumerate() {
+            bar.add_content(entry.alignment, Vec::new())?;
+            let target = widget_target(entry.widget.name(), idx);
+            match entry.widget.into_stream() {
+                Ok(stream) => {
+                    streams.insert(idx, stream);
+                }
+                Err(err) => {
+                    error!(target: &target, "Error starting widget: {err:#}");
+                    if let Some(display) = &options.error_display {
+                        bar.update_content(idx, vec![display.text(&format!("{err:#}"))])?;
+                    }
+                }
+            }
+            targets.push(target);
+        }
+
+        // Stop listening to each widget once it has yielded its first content.
+        let deadline = Instant::now() + RENDER_ONCE_TIMEOUT;
+        while let Ok(Some((idx, result))) = time::timeout_at(deadline, streams.next()).await {
+            streams.remove(&idx);
+            match result {
+                Err(err) => {
+                    error!(target: &targets[idx], "Error from widget: {err:#}");
+                    if let Some(display) = &options.error_display {
+                        bar.update_content(idx, vec![display.text(&format!("{err:#}"))])?;
+                    }
+                }
+                Ok(texts) => bar.update_content(idx, texts)?,
+            }
         }
 
-        let mut event_stream = XcbEventStream::new(bar.connection().clone())?;
-        task::spawn_local(async move {
+        bar.backend().write_png(path)
+    }
+
+    async fn run_headless_inner(mut self, format: OutputFormat) -> Result<()> {
+        let config_path = self.config_path.take();
+        let mut config_changes: Pin<Box<dyn Stream<Item = ()>>> = match &config_path {
+            Some(path) => Box::pin(FileWatchStream::new(path)?),
+            None => Box::pin(futures::stream::pending()),
+        };
+
+        let (options, entries) = self.into_parts();
+        // There's no bar to embed windows in.
+        let mut widgets = RunningWidgets::start(entries, false);
+        let mut requests = listen(&options.name);
+
+        let mut printer = Printer::new(format, options, widgets.alignments.clone());
+        widgets.show_errors(&mut printer);
+        let mut stdout = io::stdout();
+        printer.write_header(&mut stdout)?;
+        loop {
+            // Write whatever has changed since the last time round. Once
+            // stdout is closed (e.g. because the bar reading it has exited),
+            // there's no point carrying on.
+            printer.write(&mut stdout)?;
+            tokio::select! {
+                Some((idx, event)) = widgets.streams.next() => {
+                    widgets.handle_event(idx, event, &mut printer);
+                }
+                Some(idx) = widgets.restarts.next() => widgets.restart(idx, &mut printer),
+                Some((signal, ())) = widgets.signals.next() => {
+                    widgets.handle_signal(signal, &mut printer);
+                }
+                Some(()) = config_changes.next() => {
+                    if let Some(path) = &config_path {
+                        // Errors have already been logged.
+                        let _ = reload_widgets(path, &mut printer, &mut widgets);
+                    }
+                }
+                Some((request, respond)) = requests.next() => {
+                    let config_path = config_path.as_deref();
+                    let response =
+                        handle_request(request, &mut widgets, &mut printer, config_path);
+                    let _ = respond.send(response);
+                }
+            }
+        }
+    }
+
+    // Splits the Cnx instance into the options of its bars and the widgets
+    // to show on them.
+    fn into_parts(self) -> (BarOptions, Vec<WidgetEntry>) {
+        let placement = match self.monitors {
+            Some(monitors) => Placement::Monitors(monitors),
+            None => Placement::Single {
+                width: self.width,
+                offset: self.offset,
+            },
+        };
+        let options = BarOptions {
+            position: self.position,
+            placement,
+            name: self.name,
+            height: self.height,
+            error_display: self.error_display,
+        };
+        (options, self.widgets)
+    }
+
+    async fn run_inner(mut self) -> Result<()> {
+        let config_path = self.config_path.take();
+        let reconnect = self.reconnect;
+        let mut config_changes: Pin<Box<dyn Stream<Item = ()>>> = match &config_path {
+            Some(path) => Box::pin(FileWatchStream::new(path)?),
+            None => Box::pin(futures::stream::pending()),
+        };
+
+        let (options, entries) = self.into_parts();
+        let mut widgets = RunningWidgets::start(entries, true);
+
+        // The socket is named after the bar as it starts, even if the name
+        // changes when the config is reloaded.
+        let mut requests = listen(&options.name);
+
+        let (conn, screen_idx) = connect()?;
+        let mut bars = Bars::new(
+            conn.clone(),
+            screen_idx,
+            options,
+            widgets.alignments.clone(),
+        )?;
+        widgets.show_errors(&mut bars);
+
+        let mut event_stream = XcbEventStream::new(conn.clone())?;
+        let task: task::JoinHandle<Result<()>> = task::spawn_local(async move {
+            let mut conn = conn;
             loop {
+                // Embed the windows of widgets that have started since the
+                // last time round, and forget those of widgets that stopped.
+                for (idx, window) in widgets.window_changes.drain(..) {
+                    bars.set_window(idx, window);
+                }
                 tokio::select! {
-                    // Pass each XCB event to the Bar.
-                    Some(event) = event_stream.next() => {
-                        if let Err(err) = bar.process_event(event) {
-                            println!("Error processing XCB event: {err}");
+                    // Pass each XCB event to the Bars, and any mouse events
+                    // on a widget's texts on to that widget.
+                    event = event_stream.next() => {
+                        let event = match event {
+                            Some(event) => event,
+                            // The connection is gone, so either give up or
+                            // start over with a new one.
+                            None => {
+                                let lost = connection_lost(&conn);
+                                if !reconnect {
+                                    return Err(lost.into());
+                                }
+                                warn!("{lost}, reconnecting");
+                                let (new_conn, screen_idx) = reconnect_to_x().await;
+                                bars.reconnect(new_conn.clone(), screen_idx)?;
+                                event_stream = XcbEventStream::new(new_conn.clone())?;
+                                conn = new_conn;
+                                continue;
+                            }
+                        };
+                        match bars.process_event(event) {
+                            Err(err) => error!("Error processing XCB event: {err:#}"),
+                            Ok(Some((idx, mouse_event))) => {
+                                widgets.handle_mouse_event(idx, mouse_event);
+                            }
+                            Ok(None) => {}
                         }
                     },
 
                     // Each time a widget yields new values, pass to the bar.
-                    // Ignore (but log) any errors from widgets.
-                    Some((idx, result)) = widgets.next() => {
-                        match result {
-                            Err(err) => println!("Error from widget {idx}: {err}"),
-                            Ok(texts) => {
-                                if let Err(err) = bar.update_content(idx, texts) {
-                                    println!("Error updating widget {idx}: {err}");
-                                }
-                            }
+                    // Errors from widgets are logged and shown on the bar,
+                    // and widgets that fail, panic or stop are restarted as
+                    // their restart policy says.
+                    Some((idx, event)) = widgets.streams.next() => {
+                        widgets.handle_event(idx, event, &mut bars);
+                    }
+
+                    // Replace stopped widgets once they have waited long
+                    // enough.
+                    Some(idx) = widgets.restarts.next() => widgets.restart(idx, &mut bars),
+
+                    // Swap in the new widgets when the config file changes.
+                    // (This stream never yields if there is no config file).
+                    Some(()) = config_changes.next() => {
+                        if let Some(path) = &config_path {
+                            // Errors have already been logged.
+                            let _ = reload_widgets(path, &mut bars, &mut widgets);
+                        }
+                    }
+
+                    // Refresh the widgets bound to a real-time signal when
+                    // the bar receives it.
+                    Some((signal, ())) = widgets.signals.next() => {
+                        widgets.handle_signal(signal, &mut bars);
+                    }
+
+                    // Carry out requests from `rusty-bar msg` and the like.
+                    Some((request, respond)) = requests.next() => {
+                        let config_path = config_path.as_deref();
+                        let response =
+                            handle_request(request, &mut widgets, &mut bars, config_path);
+                        // The client may have gone away, which is fine.
+                        let _ = respond.send(response);
+                    }
+                }
+            }
+        });
+        task.await??;
+
+        Ok(())
+    }
+}
+
+// Listens for requests on the socket of the bar called `name`. Without it,
+// the bar can't be controlled by scripts, but otherwise works fine.
+fn listen(name: &str) -> Pin<Box<dyn Stream<Item = Incoming>>> {
+    match Server::new(name) {
+        Ok(server) => Box::pin(server),
+        Err(err) => {
+            warn!("Not listening for requests: {err:#}");
+            Box::pin(futures::stream::pending())
+        }
+    }
+}
+
+// Connects to the X server, waiting for it to come back if it isn't there.
+async fn reconnect_to_x() -> (Rc<ewmh::Connection>, usize) {
+    loop {
+        match connect() {
+            Ok(connection) => return connection,
+            Err(err) => {
+                warn!("{err:#}, retrying in {}s", RECONNECT_DELAY.as_secs());
+                time::sleep(RECONNECT_DELAY).await;
+            }
+        }
+    }
+}
+
+// The streams and mouse handlers of the widgets of a running `Cnx`, keyed by
+// the index of each widget, along with what's needed to restart them.
+struct RunningWidgets {
+    alignments: Vec<Alignment>,
+    // The name of each widget, see `Widget::name()`.
+    names: Vec<&'static str>,
+    // The log target of each widget, see `logging::widget_target()`.
+    targets: Vec<String>,
+    // Whether widgets can embed windows, which they can't without a bar to
+    // embed them in.
+    embed: bool,
+    windows: HashMap<usize, xcb::Window>,
+    // The windows embedded (`Some`) or removed (`None`) since the bars were
+    // last told about them, see `Bars::set_window()`.
+    window_changes: Vec<(usize, Option<xcb::Window>)>,
+    streams: StreamMap<usize, SupervisedStream>,
+    mouse_handlers: HashMap<usize, MouseHandler>,
+    errors: RateLimiter<usize>,
+
+    factories: HashMap<usize, WidgetFactory>,
+    // When each widget is restarted. Widgets that can't be restarted have
+    // `RestartPolicy::Never`.
+    policies: Vec<RestartPolicy>,
+    // The most recent error of each widget that has failed (and not yet
+    // recovered).
+    failures: HashMap<usize, String>,
+    // How many times in a row each widget has been stopped without yielding
+    // any content, which sets how long to wait before restarting it.
+    attempts: HashMap<usize, u32>,
+    // Each yields the index of a stopped widget once it's time to restart it.
+    restarts: FuturesUnordered<Pin<Box<dyn Future<Output = usize>>>>,
+
+    // The real-time signal each widget is refreshed by, see
+    // `WidgetEntry::refresh_signal()`.
+    refresh_signals: Vec<Option<u32>>,
+    // Each yields when the bar receives the real-time signal it's keyed by.
+    signals: StreamMap<u32, Signals>,
+}
+
+impl RunningWidgets {
+    // Starts each widget. Widgets that fail to start are restarted later, if
+    // they can be, so `show_errors()` should be called once there are bars
+    // to show them on.
+    //
+    // Unless `embed` is set, widgets aren't asked for a window to embed, so
+    // those that need one (like `Tray`) fail to start.
+    fn start(entries: Vec<WidgetEntry>, embed: bool) -> RunningWidgets {
+        let mut widgets = RunningWidgets {
+            alignments: Vec::with_capacity(entries.len()),
+            names: Vec::with_capacity(entries.len()),
+            targets: Vec::with_capacity(entries.len()),
+            embed,
+            windows: HashMap::new(),
+            window_changes: Vec::new(),
+            streams: StreamMap::with_capacity(entries.len()),
+            mouse_handlers: HashMap::new(),
+            errors: RateLimiter::new(),
+            factories: HashMap::new(),
+            policies: Vec::with_capacity(entries.len()),
+            failures: HashMap::new(),
+            attempts: HashMap::new(),
+            restarts: FuturesUnordered::new(),
+            refresh_signals: Vec::with_capacity(entries.len()),
+            signals: StreamMap::new(),
+        };
+        for (idx, entry) in entries.into_iter().enumerate() {
+            let name = entry.widget.name();
+            widgets.names.push(name);
+            widgets.targets.push(widget_target(name, idx));
+            widgets.alignments.push(entry.alignment);
+            widgets.refresh_signals.push(entry.refresh_signal);
+            // Widgets bound to the same signal share its stream.
+            if let Some(signal) = entry.refresh_signal {
+                if !widgets.signals.contains_key(&signal) {
+                    match signal::realtime(signal) {
+                        Ok(signals) => {
+                            widgets.signals.insert(signal, signals);
                         }
+                        Err(err) => error!(target: &widgets.targets[idx], "{err:#}"),
                     }
                 }
             }
-        })
-        .await?;
+            match entry.factory {
+                Some(factory) => {
+                    widgets.factories.insert(idx, factory);
+                    widgets.policies.push(entry.restart_policy);
+                }
+                None => widgets.policies.push(RestartPolicy::Never),
+            }
+            if let Err(err) = widgets.start_widget(idx, entry.widget) {
+                widgets.record_failure(idx, err.context("Failed to start widget"));
+                widgets.stop(idx, widgets.policies[idx] != RestartPolicy::Never);
+            }
+        }
+        widgets
+    }
 
+    fn start_widget(&mut self, idx: usize, mut widget: Box<dyn Widget>) -> Result<()> {
+        match widget.mouse_handler() {
+            Some(handler) => self.mouse_handlers.insert(idx, handler),
+            None => self.mouse_handlers.remove(&idx),
+        };
+        if self.embed {
+            if let Some(window) = catch_panic(|| widget.embedded_window())? {
+                self.windows.insert(idx, window);
+                self.window_changes.push((idx, Some(window)));
+            }
+        }
+        let stream = catch_panic(|| widget.into_stream())?;
+        self.streams.insert(idx, supervise(stream));
         Ok(())
     }
+
+    // Handles an event from the stream of the widget at `idx`.
+    fn handle_event(&mut self, idx: usize, event: WidgetEvent, output: &mut dyn Output) {
+        match event {
+            WidgetEvent::Update(Ok(texts)) => {
+                self.recovered(idx);
+                if let Err(err) = output.update_content(idx, texts) {
+                    error!(target: &self.targets[idx], "Error updating widget: {err:#}");
+                }
+            }
+            // A widget that can't be restarted may still recover by itself.
+            WidgetEvent::Update(Err(err)) => {
+                let stop = self.policies[idx] != RestartPolicy::Never;
+                self.fail(idx, err, stop, output);
+            }
+            WidgetEvent::Panicked(message) => {
+                self.fail(idx, anyhow!("Widget panicked: {message}"), true, output);
+            }
+            WidgetEvent::Ended => {
+                let restart = self.policies[idx] == RestartPolicy::Always;
+                if restart {
+                    warn!(target: &self.targets[idx], "Widget stopped, restarting it");
+                } else {
+                    warn!(target: &self.targets[idx], "Widget stopped");
+                }
+                self.stop(idx, restart);
+            }
+        }
+    }
+
+    // Passes a mouse event on the texts of the widget at `idx` to the
+    // widget's mouse handler, if it has one.
+    fn handle_mouse_event(&mut self, idx: usize, event: MouseEvent) {
+        if let Some(handler) = self.mouse_handlers.get_mut(&idx) {
+            if let Err(err) = catch_panic(|| handler(event)) {
+                warn!(target: &self.targets[idx], "Error handling mouse event: {err:#}");
+            }
+        }
+    }
+
+    // Logs that the widget at `idx` has failed with `err`.
+    //
+    // Returns the error message.
+    fn record_failure(&mut self, idx: usize, err: Error) -> String {
+        let message = format!("{err:#}");
+        if let Some(message) = self.errors.filter(idx, message.clone()) {
+            error!(target: &self.targets[idx], "{message}");
+        }
+        self.failures.insert(idx, message.clone());
+        message
+    }
+
+    // Stops the widget at `idx`, along with its window if it has one, and
+    // schedules a restart if `restart` is set and the widget can be restarted.
+    fn stop(&mut self, idx: usize, restart: bool) {
+        self.streams.remove(&idx);
+        if self.windows.remove(&idx).is_some() {
+            self.window_changes.push((idx, None));
+        }
+
+        if restart && self.factories.contains_key(&idx) {
+            let attempts = self.attempts.entry(idx).or_insert(0);
+            *attempts += 1;
+            let delay = restart_delay(*attempts);
+            self.restarts.push(Box::pin(async move {
+                time::sleep(delay).await;
+                idx
+            }));
+        }
+    }
+
+    // Handles the widget at `idx` yielding (or returning) an error, stopping
+    // it if `stop` is set.
+    fn fail(&mut self, idx: usize, err: Error, stop: bool, output: &mut dyn Output) {
+        let message = self.record_failure(idx, err);
+        if stop {
+            let restart = self.policies[idx] != RestartPolicy::Never;
+            self.stop(idx, restart);
+        }
+        if let Err(err) = output.show_error(idx, &message) {
+            error!(target: &self.targets[idx], "Error showing widget error: {err:#}");
+        }
+    }
+
+    // Shows the errors of widgets that failed before there were any bars.
+    fn show_errors(&mut self, output: &mut dyn Output) {
+        for (&idx, message) in &self.failures {
+            if let Err(err) = output.show_error(idx, message) {
+                error!(target: &self.targets[idx], "Error showing widget error: {err:#}");
+            }
+        }
+    }
+
+    // Forgets the failures of the widget at `idx` once it yields content.
+    fn recovered(&mut self, idx: usize) {
+        if self.failures.remove(&idx).is_some() {
+            info!(target: &self.targets[idx], "Widget recovered");
+        }
+        self.attempts.remove(&idx);
+        self.errors.reset(&idx);
+    }
+
+    // Replaces the stopped widget at `idx` with a new instance, unless it has
+    // been restarted already.
+    fn restart(&mut self, idx: usize, output: &mut dyn Output) {
+        if self.streams.contains_key(&idx) {
+            return;
+        }
+        let widget = match self.factories.get_mut(&idx) {
+            Some(factory) => catch_panic(|| factory()),
+            None => return,
+        };
+        let started = widget.and_then(|widget| self.start_widget(idx, widget));
+        if let Err(err) = started {
+            self.fail(idx, err.context("Failed to restart widget"), true, output);
+        }
+    }
+
+    // Describes each widget, for `Request::List`.
+    fn list(&self, output: &dyn Output) -> Vec<WidgetInfo> {
+        (0..self.names.len())
+            .map(|idx| WidgetInfo {
+                index: idx,
+                name: self.names[idx].to_owned(),
+                texts: output
+                    .content(idx)
+                    .iter()
+                    .map(|text| text.text.clone())
+                    .collect(),
+                error: self.failures.get(&idx).cloned(),
+            })
+            .collect()
+    }
+
+    // Restarts the selected widgets straight away, for `Request::Refresh`.
+    fn refresh(&mut self, widget: &WidgetId, output: &mut dyn Output) -> Result<()> {
+        let selected: Vec<usize> = match widget {
+            WidgetId::Index(idx) if *idx < self.names.len() => vec![*idx],
+            WidgetId::Index(idx) => return Err(anyhow!("There is no widget {idx}")),
+            WidgetId::Name(name) => (0..self.names.len())
+                .filter(|&idx| self.names[idx] == name)
+                .collect(),
+        };
+        if selected.is_empty() {
+            return Err(anyhow!("There is no {widget:?} widget"));
+        }
+        for idx in selected {
+            self.refresh_widget(idx, output)?;
+        }
+        Ok(())
+    }
+
+    // Refreshes the widgets bound to the real-time signal SIGRTMIN+`signal`.
+    fn handle_signal(&mut self, signal: u32, output: &mut dyn Output) {
+        let selected: Vec<usize> = (0..self.refresh_signals.len())
+            .filter(|&idx| self.refresh_signals[idx] == Some(signal))
+            .collect();
+        for idx in selected {
+            if let Err(err) = self.refresh_widget(idx, output) {
+                warn!(target: &self.targets[idx], "{err:#}");
+            }
+        }
+    }
+
+    // Replaces the widget at `idx` with a new instance straight away.
+    fn refresh_widget(&mut self, idx: usize, output: &mut dyn Output) -> Result<()> {
+        if !self.factories.contains_key(&idx) {
+            return Err(anyhow!("Widget {idx} can't be restarted"));
+        }
+        self.stop(idx, false);
+        self.restart(idx, output);
+        Ok(())
+    }
+}
+
+// Returns how long to wait before restarting a widget that has been stopped
+// `attempts` times in a row.
+fn restart_delay(attempts: u32) -> Duration {
+    let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+    RESTART_DELAY.saturating_mul(factor).min(MAX_RESTART_DELAY)
+}
+
+// Reloads the config file at `path`, replacing the running widgets with the
+// ones it describes. If that fails, the error is logged as well as returned,
+// and the previous widgets are kept.
+fn reload_widgets(
+    path: &Path,
+    output: &mut dyn Output,
+    widgets: &mut RunningWidgets,
+) -> Result<()> {
+    match reload(path, output, widgets) {
+        Ok(()) => {
+            info!("Reloaded {}", path.display());
+            Ok(())
+        }
+        Err(err) => {
+            error!(
+                "Error reloading {}, keeping previous config: {err:#}",
+                path.display()
+            );
+            Err(err)
+        }
+    }
+}
+
+// Carries out a request received on the bar's socket.
+fn handle_request(
+    request: Request,
+    widgets: &mut RunningWidgets,
+    output: &mut dyn Output,
+    config_path: Option<&Path>,
+) -> Response {
+    let result = match request {
+        Request::List => return Response::Widgets(widgets.list(output)),
+        Request::Refresh { widget } => widgets.refresh(&widget, output),
+        Request::Hide => output.set_visible(false),
+        Request::Show => output.set_visible(true),
+        Request::Toggle => output.set_visible(!output.visible()),
+        Request::Reload => match config_path {
+            Some(path) => reload_widgets(path, output, widgets),
+            None => Err(anyhow!("There is no config file to reload")),
+        },
+        Request::Set { widget, text, ttl } => {
+            ipc_text::set(&widget, text, ttl.map(Duration::from_secs))
+        }
+    };
+    match result {
+        Ok(()) => Response::Ok,
+        Err(err) => Response::Error(format!("{err:#}")),
+    }
+}
+
+// Loads the config file at `path` and replaces `widgets` with the ones it
+// describes.
+//
+// If the config can't be loaded, the error is returned and the widgets and
+// output are left untouched. Otherwise, the widgets are always replaced and
+// the output is reconfigured for them.
+fn reload(path: &Path, output: &mut dyn Output, widgets: &mut RunningWidgets) -> Result<()> {
+    let (options, entries) = Config::load(path)?.into_cnx()?.into_parts();
+    // The previous widgets are dropped before the new ones start, so that
+    // they've let go of anything the new ones need, like the tray's
+    // selection.
+    let embed = widgets.embed;
+    *widgets = RunningWidgets::start(Vec::new(), embed);
+    *widgets = RunningWidgets::start(entries, embed);
+    ipc_text::forget_stopped();
+
+    let reconfigured = output.reconfigure(options, widgets.alignments.clone());
+    if let Err(err) = reconfigured {
+        error!("Error reconfiguring bar after reloading config: {err:#}");
+    }
+    widgets.show_errors(output);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{restart_delay, Cnx, RunningWidgets, Widget, WidgetStream};
+    use crate::bar::{Alignment, Position};
+    use crate::bars::BarOptions;
+    use crate::output::Output;
+    use crate::supervisor::WidgetEvent;
+    use crate::text::Text;
+    use anyhow::{anyhow, Result};
+    use std::time::Duration;
+
+    // A widget that never yields anything.
+    struct Idle;
+
+    impl Widget for Idle {
+        fn into_stream(self: Box<Self>) -> Result<WidgetStream> {
+            Ok(Box::pin(futures::stream::pending()))
+        }
+    }
+
+    // Keeps whatever the widgets show, in place of a bar.
+    struct FakeOutput {
+        contents: Vec<Vec<Text>>,
+        errors: Vec<(usize, String)>,
+    }
+
+    impl Output for FakeOutput {
+        fn reconfigure(&mut self, _options: BarOptions, alignments: Vec<Alignment>) -> Result<()> {
+            self.contents = vec![Vec::new(); alignments.len()];
+            Ok(())
+        }
+
+        fn content(&self, idx: usize) -> &[Text] {
+            &self.contents[idx]
+        }
+
+        fn update_content(&mut self, idx: usize, content: Vec<Text>) -> Result<()> {
+            self.contents[idx] = content;
+            Ok(())
+        }
+
+        fn show_error(&mut self, idx: usize, error: &str) -> Result<()> {
+            self.errors.push((idx, error.to_owned()));
+            Ok(())
+        }
+
+        fn set_visible(&mut self, _visible: bool) -> Result<()> {
+            Ok(())
+        }
+
+        fn visible(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn restart_delays() {
+        let delays: Vec<u64> = (1..=10)
+            .map(|attempts| restart_delay(attempts).as_secs())
+            .collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 32, 64, 128, 256, 300]);
+        assert_eq!(restart_delay(u32::MAX), Duration::from_secs(300));
+    }
+
+    #[tokio::test]
+    async fn recovers_after_failure() {
+        let mut cnx = Cnx::new(Position::Top);
+        cnx.add_widget_fn(|| Ok(Box::new(Idle))).unwrap();
+        let (_, entries) = cnx.into_parts();
+        let mut widgets = RunningWidgets::start(entries, false);
+        let mut output = FakeOutput {
+            contents: vec![Vec::new()],
+            errors: Vec::new(),
+        };
+
+        let failure = WidgetEvent::Update(Err(anyhow!("No battery")));
+        widgets.handle_event(0, failure, &mut output);
+        assert_eq!(output.errors, vec![(0, "No battery".to_owned())]);
+        assert_eq!(widgets.failures.get(&0).unwrap(), "No battery");
+        assert_eq!(widgets.attempts.get(&0), Some(&1));
+        assert!(!widgets.streams.contains_key(&0));
+
+        // Once restarted, the widget is only forgiven when it yields content.
+        widgets.restart(0, &mut output);
+        assert!(widgets.streams.contains_key(&0));
+        assert!(widgets.failures.contains_key(&0));
+
+        widgets.handle_event(0, WidgetEvent::Update(Ok(Vec::new())), &mut output);
+        assert!(widgets.failures.is_empty());
+        assert!(widgets.attempts.is_empty());
+    }
 }
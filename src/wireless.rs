@@ -1,10 +1,9 @@
 use anyhow::Result;
+use crate::schedule;
 use crate::text::{Attributes, Text, Threshold};
 use crate::widget::{Widget, WidgetStream};
 use iwlib::*;
 use std::time::Duration;
-use tokio::time;
-use tokio_stream::wrappers::IntervalStream;
 use tokio_stream::StreamExt;
 
 /// Wireless widget to show wireless information for a particular ESSID
@@ -47,6 +46,7 @@ impl Wireless {
     ///     fg_color: Color::white(),
     ///     bg_color: None,
     ///     padding: Padding::new(8.0, 8.0, 0.0, 0.0),
+    ///     valign: VerticalAlignment::Center,
     /// };
     ///
     /// let mut cnx = Cnx::new(Position::Top);
@@ -64,6 +64,15 @@ impl Wireless {
         }
     }
 
+    /// Sets how often the network and its signal quality are updated. The
+    /// default is an hour.
+    pub fn with_interval(self, update_interval: Duration) -> Self {
+        Self {
+            update_interval,
+            ..self
+        }
+    }
+
     fn tick(&self) -> Vec<Text> {
         let wireless_info = get_wireless_info(self.interface.clone());
 
@@ -99,8 +108,8 @@ impl Wireless {
 
 impl Widget for Wireless {
     fn into_stream(self: Box<Self>) -> Result<WidgetStream> {
-        let interval = time::interval(self.update_interval);
-        let stream = IntervalStream::new(interval).map(move |_| Ok(self.tick()));
+        let ticks = schedule::ticks(self.update_interval);
+        let stream = ticks.map(move |_| Ok(self.tick()));
 
         Ok(Box::pin(stream))
     }
@@ -1,4 +1,6 @@
 use anyhow::{anyhow, Context as _AnyhowContext, Result};
+use log::error;
+use std::fmt;
 use std::os::unix::io::AsRawFd;
 use std::os::unix::io::RawFd;
 use std::pin::Pin;
@@ -24,10 +26,12 @@ impl AsRawFd for XcbEvented {
 }
 
 // A `Stream` of `xcb::GenericEvent` for the provided `xcb::Connection`.
+//
+// The stream ends if the connection is lost, after which
+// `connection_lost()` says why.
 pub struct XcbEventStream {
     conn: Rc<ewmh::Connection>,
     poll: AsyncFd<XcbEvented>,
-    would_block: bool,
 }
 
 impl XcbEventStream {
@@ -35,11 +39,7 @@ impl XcbEventStream {
         let evented = XcbEvented(conn.clone());
         let poll = AsyncFd::with_interest(evented, tokio::io::Interest::READABLE)?;
 
-        Ok(XcbEventStream {
-            conn,
-            poll,
-            would_block: true,
-        })
+        Ok(XcbEventStream { conn, poll })
     }
 }
 
@@ -48,37 +48,106 @@ impl Stream for XcbEventStream {
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
         let self_ = &mut *self;
-        let mut ready = None;
-        if self_.would_block {
+        loop {
+            // XCB may have already read events off the socket (e.g. while
+            // waiting for a reply), so check for those before waiting.
+            if let Some(event) = self_.conn.poll_for_event() {
+                return Poll::Ready(Some(event));
+            }
+            // Once the connection is broken there will be no more events,
+            // but the socket stays readable.
+            if self_.conn.has_error().is_err() {
+                return Poll::Ready(None);
+            }
             match self_.poll.poll_read_ready(cx) {
-                Poll::Ready(Ok(r)) => {
-                    ready = Some(r);
-                    self_.would_block = false;
-                }
+                Poll::Ready(Ok(mut ready)) => ready.clear_ready(),
                 Poll::Ready(Err(e)) => {
-                    // Unsure when this would happen:
-                    panic!("Error polling xcb::Connection: {e}");
+                    error!("Error polling xcb::Connection: {e}");
+                    return Poll::Ready(None);
                 }
                 Poll::Pending => return Poll::Pending,
             }
         }
-        match self_.conn.poll_for_event() {
-            Some(event) => Poll::Ready(Some(event)),
-            None => {
-                self_.would_block = true;
-                match ready {
-                    None => self.poll_next(cx),
-                    Some(mut r) => {
-                        r.clear_ready();
-                        self.poll_next(cx)
-                    }
-                }
-                // self.poll_next(cx)
-            }
-        }
     }
 }
 
+/// The error returned when the connection to the X server is lost, e.g.
+/// because the X server exited.
+#[derive(Debug)]
+pub struct ConnectionLost(String);
+
+impl fmt::Display for ConnectionLost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Lost connection to the X server: {}", self.0)
+    }
+}
+
+impl std::error::Error for ConnectionLost {}
+
+// Returns why `conn` can no longer be used, once its `XcbEventStream` has
+// ended.
+pub(crate) fn connection_lost(conn: &xcb::Connection) -> ConnectionLost {
+    match conn.has_error() {
+        Err(err) => ConnectionLost(err.to_string()),
+        Ok(()) => ConnectionLost("failed to poll the connection".to_owned()),
+    }
+}
+
+// X protocol errors, indexed by their error code.
+const ERROR_NAMES: [&str; 18] = [
+    "Success",
+    "BadRequest",
+    "BadValue",
+    "BadWindow",
+    "BadPixmap",
+    "BadAtom",
+    "BadCursor",
+    "BadFont",
+    "BadMatch",
+    "BadDrawable",
+    "BadAccess",
+    "BadAlloc",
+    "BadColormap",
+    "BadGContext",
+    "BadIDChoice",
+    "BadName",
+    "BadLength",
+    "BadImplementation",
+];
+
+// If `event` is an X protocol error, returns a description of it.
+//
+// Errors for requests whose reply we don't wait for (e.g. configuring a
+// window that has since been destroyed) arrive as events.
+pub(crate) fn describe_error(event: &xcb::GenericEvent) -> Option<String> {
+    if event.response_type() != 0 {
+        return None;
+    }
+    // Errors have the same size as events, with their own layout.
+    let error = unsafe { &*(event.ptr as *const xcb::ffi::base::xcb_generic_error_t) };
+    let name = ERROR_NAMES
+        .get(usize::from(error.error_code))
+        .copied()
+        .unwrap_or("unknown error");
+    Some(format!(
+        "X error {name} ({}) for request {}.{} on resource {:#x} (sequence {})",
+        error.error_code, error.major_code, error.minor_code, error.resource_id, error.sequence,
+    ))
+}
+
+// Connects to the X server.
+//
+// Returns the connection wrapped in an `ewmh::Connection`, along with the index
+// of the default screen.
+pub fn connect() -> Result<(Rc<ewmh::Connection>, usize)> {
+    let (xcb_conn, screen_idx) =
+        xcb::Connection::connect(None).context("Failed to connect to X server")?;
+    let ewmh_conn = ewmh::Connection::connect(xcb_conn)
+        .map_err(|(e, _)| e)
+        .context("Failed to wrap xcb::Connection in ewmh::Connection")?;
+    Ok((Rc::new(ewmh_conn), screen_idx as usize))
+}
+
 // A `Stream` that listens to `PROPERTY_CHANGE` notifications.
 //
 // By default it listens to `PROPERTY_CHANGE` notifications for the provided
@@ -88,18 +157,13 @@ impl Stream for XcbEventStream {
 pub fn xcb_properties_stream(
     properties: &[&str],
 ) -> Result<(Rc<ewmh::Connection>, impl Stream<Item = ()>)> {
-    let (xcb_conn, screen_idx) =
-        xcb::Connection::connect(None).context("Failed to connect to X server")?;
-    let root_window = xcb_conn
+    let (conn, screen_idx) = connect()?;
+    let root_window = conn
         .get_setup()
         .roots()
-        .nth(screen_idx as usize)
+        .nth(screen_idx)
         .ok_or_else(|| anyhow!("Invalid screen"))?
         .root();
-    let ewmh_conn = ewmh::Connection::connect(xcb_conn)
-        .map_err(|(e, _)| e)
-        .context("Failed to wrap xcb::Connection in ewmh::Connection")?;
-    let conn = Rc::new(ewmh_conn);
 
     let only_if_exists = true;
     let properties = properties